@@ -1,39 +1,187 @@
 use color_eyre::eyre::{eyre, Result, WrapErr};
-use enum_dispatch::enum_dispatch;
-use hex_literal::hex;
-use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
-use std::env;
-use std::fs::File;
-use std::io;
-use std::process::Stdio;
-use tokei::{Config, Languages};
-use tokio::process::Command;
-
-const OPENGREP_VERSION: &str = "1.16.1";
-// opengrep_musllinux_x86 from https://github.com/opengrep/opengrep/releases
-const OPENGREP_CHECKSUM: [u8; 32] =
-    hex!("888022d917da27f568113073a93bdfb2dd222f7095fa689f012d96819279a611");
-const OPENGREP_RULES_URI: &str =
-    "https://github.com/amplify-security/opengrep-rules/releases/download/latest/rules.json";
+use serde::{Deserialize, Deserializer, Serialize};
+use std::time::Duration;
+
+use crate::metrics::CodeMetrics;
+use crate::tools::opengrep::report::Report;
+use crate::tools::process::Stdout;
 
 const HEADER_X_AMPLIFY_CODE_LINES: &str = "X-Amplify-Code-Lines";
+const HEADER_X_AMPLIFY_SCAN_PATHS: &str = "X-Amplify-Scan-Paths";
+const HEADER_X_AMPLIFY_PROJECT_PATH: &str = "X-Amplify-Project-Path";
+const HEADER_X_AMPLIFY_RULES_DIGEST: &str = "X-Amplify-Rules-Digest";
+const HEADER_X_AMPLIFY_SCAN_KIND: &str = "X-Amplify-Scan-Kind";
+const HEADER_X_AMPLIFY_RUNNER_VERSION: &str = "X-Amplify-Runner-Version";
+const HEADER_X_AMPLIFY_SUPPORTED_TOOLS: &str = "X-Amplify-Supported-Tools";
+const HEADER_X_AMPLIFY_SUPPORTED_ARTIFACTS: &str = "X-Amplify-Supported-Artifacts";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AmplifyConfigResponse {
-    pub tools: Vec<Tools>,
+    #[serde(deserialize_with = "deserialize_tools")]
+    pub tools: Vec<ToolConfig>,
     pub merge_comments_enabled: bool,
     pub merge_approvals_enabled: bool,
     pub deleted: bool,
+    /// Page of the project in Amplify, if the API provides one.
+    #[serde(default)]
+    pub project_url: Option<String>,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+impl AmplifyConfigResponse {
+    /// Check that the configuration gives the runner something to do. A
+    /// deleted project has nothing to scan, so it's accepted as is.
+    fn validate(self) -> Result<Self> {
+        if !self.deleted && self.tools.is_empty() {
+            return Err(eyre!(
+                "Received a configuration with no tools supported by this runner."
+            ));
+        }
+        Ok(self)
+    }
+
+    /// Project-level settings that tools and reporting may depend on.
+    pub fn settings(&self) -> ProjectSettings {
+        ProjectSettings {
+            merge_comments_enabled: self.merge_comments_enabled,
+            merge_approvals_enabled: self.merge_approvals_enabled,
+        }
+    }
+}
+
+/// Feature flags configured for the project in Amplify.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct ProjectSettings {
+    pub merge_comments_enabled: bool,
+    pub merge_approvals_enabled: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum Tools {
     Semgrep,
     Uname,
 }
 
+impl Tools {
+    /// Every tool this runner knows how to run.
+    pub const ALL: [Tools; 2] = [Tools::Semgrep, Tools::Uname];
+
+    /// Name of the tool as used by Amplify's API.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Tools::Semgrep => "SEMGREP",
+            Tools::Uname => "UNAME",
+        }
+    }
+
+    /// Look up a tool by the name Amplify's API uses for it.
+    pub fn from_name(name: &str) -> Option<Tools> {
+        Tools::ALL.into_iter().find(|tool| tool.as_str() == name)
+    }
+}
+
+/// Options that can be set for an individual tool in the project
+/// configuration. Every option is optional and defaults to the tool's
+/// built-in behavior.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolOptions {
+    /// Additional rule packs or rule files to run alongside the defaults.
+    pub rules: Vec<String>,
+    /// Paths or glob patterns to exclude from the scan.
+    pub exclude: Vec<String>,
+    /// Maximum time in seconds that the tool may run for.
+    pub timeout: Option<u64>,
+    /// Maximum memory in megabytes that the tool may use.
+    pub max_memory_mb: Option<u64>,
+    /// Maximum CPU time in seconds that the tool may use.
+    pub max_cpu_seconds: Option<u64>,
+    /// Extra command-line arguments passed to the tool as-is.
+    pub extra_args: Vec<String>,
+    /// Release of the tool's default ruleset to use instead of the latest.
+    pub rules_version: Option<String>,
+    /// Hex-encoded SHA-256 that the default ruleset must match. When a
+    /// `rules_version` is set without one, the digest published with that
+    /// release is used.
+    pub rules_sha256: Option<String>,
+}
+
+impl ToolOptions {
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_secs)
+    }
+}
+
+/// A tool to run, along with its options.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolConfig {
+    pub name: Tools,
+    pub options: ToolOptions,
+}
+
+impl From<Tools> for ToolConfig {
+    fn from(name: Tools) -> Self {
+        ToolConfig {
+            name,
+            options: ToolOptions::default(),
+        }
+    }
+}
+
+/// A single entry of the `tools` list in a project configuration, which is
+/// either a bare tool name or an object with the tool's options.
+///
+/// Names and options are kept as they are here so that a newer API adding
+/// tools, or options of a different shape, doesn't break older runners.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ToolEntry {
+    Name(String),
+    Detailed {
+        name: String,
+        #[serde(default)]
+        options: serde_json::Value,
+    },
+}
+
+/// Read the `tools` list, skipping tools this runner doesn't know. A known
+/// tool whose options can't be read still runs, with its default options.
+fn deserialize_tools<'de, D>(deserializer: D) -> std::result::Result<Vec<ToolConfig>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let entries = Vec::<ToolEntry>::deserialize(deserializer)?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| {
+            let (name, options) = match entry {
+                ToolEntry::Name(name) => (name, serde_json::Value::Null),
+                ToolEntry::Detailed { name, options } => (name, options),
+            };
+            let Some(tool) = Tools::from_name(&name) else {
+                println!(
+                    "::warning::Skipping tool `{name}` from the project configuration because this runner version does not support it."
+                );
+                return None;
+            };
+            let options = match options {
+                serde_json::Value::Null => Ok(ToolOptions::default()),
+                options => ToolOptions::deserialize(options),
+            };
+            let options = options.unwrap_or_else(|e| {
+                println!(
+                    "::warning::Running tool `{name}` from the project configuration with its default options because this runner version does not understand its options: {e}"
+                );
+                ToolOptions::default()
+            });
+            Some(ToolConfig {
+                name: tool,
+                options,
+            })
+        })
+        .collect())
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ArtifactType {
     Json,
@@ -42,6 +190,9 @@ pub enum ArtifactType {
 }
 
 impl ArtifactType {
+    /// Artifact types that this runner's tools currently produce.
+    pub const SUPPORTED: [ArtifactType; 1] = [ArtifactType::Json];
+
     fn as_str(&self) -> &'static str {
         match self {
             ArtifactType::Json => "application/json",
@@ -50,20 +201,32 @@ impl ArtifactType {
     }
 }
 
-pub fn get_code_lines() -> usize {
-    let paths = &["."];
-    let exclude = &[];
-    let config = Config::default();
-    let mut languages = Languages::new();
-    languages.get_statistics(paths, exclude, &config);
-    let total = Languages::total(&languages);
-    total.code
+fn supported_tools_header() -> String {
+    Tools::ALL
+        .iter()
+        .map(Tools::as_str)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn supported_artifacts_header() -> String {
+    ArtifactType::SUPPORTED
+        .iter()
+        .map(ArtifactType::as_str)
+        .collect::<Vec<_>>()
+        .join(",")
 }
 
 pub async fn get_config(endpoint: String, token: String) -> Result<AmplifyConfigResponse> {
     let client = crate::common::new_http_client();
     let res = client
         .get(format!("{url}/v1.0/config", url = &endpoint))
+        .header(HEADER_X_AMPLIFY_RUNNER_VERSION, env!("CARGO_PKG_VERSION"))
+        .header(HEADER_X_AMPLIFY_SUPPORTED_TOOLS, supported_tools_header())
+        .header(
+            HEADER_X_AMPLIFY_SUPPORTED_ARTIFACTS,
+            supported_artifacts_header(),
+        )
         .bearer_auth(&token)
         .send()
         .await
@@ -73,10 +236,7 @@ pub async fn get_config(endpoint: String, token: String) -> Result<AmplifyConfig
             .json::<AmplifyConfigResponse>()
             .await
             .wrap_err("Failed to process response body for project configuration from Amplify.")?;
-        if config_data.tools.is_empty() {
-            return Err(eyre!("Received a configuration with no tools."));
-        }
-        return Ok(config_data);
+        return config_data.validate();
     }
 
     Err(eyre!(
@@ -84,170 +244,241 @@ pub async fn get_config(endpoint: String, token: String) -> Result<AmplifyConfig
     ))
 }
 
+/// Details about how an artifact was produced, sent as headers alongside it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArtifactMetadata {
+    pub code_lines: usize,
+    /// Paths that were scanned, relative to the working directory.
+    pub scan_paths: Vec<String>,
+    /// Root of the project within a monorepo that the artifact belongs to.
+    pub project_path: Option<String>,
+}
+
+/// Output of a tool run, ready to submit to Amplify.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Artifact {
+    pub artifact_type: ArtifactType,
+    /// The tool's report, uploaded straight from the file it was spilled
+    /// to if it's large.
+    pub contents: Stdout,
+    /// Digest of the ruleset that produced the artifact, as
+    /// `sha256:<hex>`, so that the results can be reproduced.
+    pub rules_digest: Option<String>,
+    pub scan_kind: ScanKind,
+    /// What the tool found, for tools whose output the runner understands.
+    /// This is only used by the runner and isn't uploaded.
+    pub findings: Option<Report>,
+}
+
+/// Whether an artifact covers the whole scanned code or only the changes
+/// since a baseline commit.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum ScanKind {
+    #[default]
+    Full,
+    Diff,
+}
+
+impl ScanKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ScanKind::Full => "full",
+            ScanKind::Diff => "diff",
+        }
+    }
+}
+
+impl Artifact {
+    pub fn new(artifact_type: ArtifactType, contents: Stdout) -> Self {
+        Artifact {
+            artifact_type,
+            contents,
+            rules_digest: None,
+            scan_kind: ScanKind::Full,
+            findings: None,
+        }
+    }
+}
+
+/// The body to upload `contents` with, streamed from its file if it has one.
+fn artifact_body(contents: &Stdout) -> Result<reqwest::Body> {
+    match contents {
+        Stdout::Memory(bytes) => Ok(reqwest::Body::from(bytes.clone())),
+        Stdout::File(path) => {
+            let file = std::fs::File::open(path)
+                .wrap_err_with(|| format!("Failed to open {}.", path.display()))?;
+            Ok(reqwest::Body::from(tokio::fs::File::from_std(file)))
+        }
+    }
+}
+
 pub async fn submit_artifact(
     endpoint: String,
     token: String,
-    artifact: String,
-    artifact_type: ArtifactType,
-    code_lines: usize,
+    artifact: Artifact,
+    metadata: &ArtifactMetadata,
+) -> Result<()> {
+    let client = reqwest::Client::new();
+    let res = crate::common::send_with_retry(|| {
+        let req = client
+            .put(format!("{url}/v1.0/artifact", url = &endpoint))
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                artifact.artifact_type.as_str(),
+            )
+            .header(HEADER_X_AMPLIFY_CODE_LINES, metadata.code_lines.to_string())
+            .header(HEADER_X_AMPLIFY_SCAN_PATHS, metadata.scan_paths.join(","))
+            .header(HEADER_X_AMPLIFY_SCAN_KIND, artifact.scan_kind.as_str());
+        let req = match &metadata.project_path {
+            Some(project_path) => req.header(HEADER_X_AMPLIFY_PROJECT_PATH, project_path),
+            None => req,
+        };
+        let req = match &artifact.rules_digest {
+            Some(digest) => req.header(HEADER_X_AMPLIFY_RULES_DIGEST, digest),
+            None => req,
+        };
+        Ok(req
+            .bearer_auth(&token)
+            .body(artifact_body(&artifact.contents)?))
+    })
+    .await
+    .wrap_err("Failed to complete request for submitting an artifact to Amplify.")?;
+    if res.status().is_success() {
+        return Ok(());
+    }
+
+    Err(eyre!(
+        "Received a non-successful {} HTTP response when submitting artifact to Amplify.",
+        res.status().as_str()
+    ))
+}
+
+/// Submit the per-language code metrics for this run, or for one project of
+/// a monorepo.
+pub async fn submit_metrics(
+    endpoint: String,
+    token: String,
+    metrics: &CodeMetrics,
+    project_path: Option<&str>,
 ) -> Result<()> {
     let client = crate::common::new_http_client();
-    let res = client
-        .put(format!("{url}/v1.0/artifact", url = &endpoint))
-        .header(reqwest::header::CONTENT_TYPE, artifact_type.as_str())
-        .header(HEADER_X_AMPLIFY_CODE_LINES, code_lines.to_string())
+    let req = client.put(format!("{url}/v1.0/metrics", url = &endpoint));
+    let req = match project_path {
+        Some(project_path) => req.header(HEADER_X_AMPLIFY_PROJECT_PATH, project_path),
+        None => req,
+    };
+    let res = req
         .bearer_auth(&token)
-        .body(artifact)
+        .json(metrics)
         .send()
         .await
-        .wrap_err("Failed to complete request for submitting an artifact to Amplify.")?;
+        .wrap_err("Failed to complete request for submitting code metrics to Amplify.")?;
     if res.status().is_success() {
-        println!("Successfully submitted tool result to Amplify.");
+        println!("Successfully submitted code metrics to Amplify.");
         return Ok(());
     }
 
     Err(eyre!(
-        "Received a non-successful {} HTTP response when submitting artifact to Amplify.",
+        "Received a non-successful {} HTTP response when submitting code metrics to Amplify.",
         res.status().as_str()
     ))
 }
 
-#[enum_dispatch(Tool)]
-pub trait ToolActions {
-    async fn setup(&self) -> Result<()>;
-    async fn launch(&self) -> Result<(ArtifactType, String)>;
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-#[enum_dispatch]
-pub enum Tool {
-    Opengrep,
-    Uname,
-}
+    #[test]
+    fn test_config_with_known_tools_deserializes() {
+        let config: AmplifyConfigResponse = serde_json::from_str(
+            r#"{"tools":["SEMGREP","UNAME"],"merge_comments_enabled":true,"merge_approvals_enabled":false,"deleted":false}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.tools,
+            vec![
+                ToolConfig::from(Tools::Semgrep),
+                ToolConfig::from(Tools::Uname)
+            ]
+        );
+        assert!(config.merge_comments_enabled);
+    }
 
-impl Tool {
-    pub fn new_from(tool: Tools) -> Tool {
-        match tool {
-            // Map Semgrep from API to Opengrep. May change later depending on
-            // if Amplify's API retroactively renames the tool for everyone.
-            Tools::Semgrep => Tool::Opengrep(Opengrep {}),
-            Tools::Uname => Tool::Uname(Uname {}),
-        }
+    #[test]
+    fn test_config_skips_unknown_tools() {
+        let config: AmplifyConfigResponse = serde_json::from_str(
+            r#"{"tools":["TRIVY","SEMGREP"],"merge_comments_enabled":false,"merge_approvals_enabled":false,"deleted":false}"#,
+        )
+        .unwrap();
+        assert_eq!(config.tools, vec![ToolConfig::from(Tools::Semgrep)]);
     }
-}
 
-#[derive(Debug, Default)]
-pub struct Opengrep {}
+    #[test]
+    fn test_config_accepts_tool_objects_with_options() {
+        let config: AmplifyConfigResponse = serde_json::from_str(
+            r#"{"tools":["UNAME",{"name":"SEMGREP","options":{"rules":["p/ci"],"exclude":["vendor/"],"timeout":600}}],"merge_comments_enabled":false,"merge_approvals_enabled":false,"deleted":false}"#,
+        )
+        .unwrap();
+        assert_eq!(config.tools.len(), 2);
+        assert_eq!(config.tools[0], ToolConfig::from(Tools::Uname));
+        let semgrep = &config.tools[1];
+        assert_eq!(semgrep.name, Tools::Semgrep);
+        assert_eq!(semgrep.options.rules, vec!["p/ci"]);
+        assert_eq!(semgrep.options.exclude, vec!["vendor/"]);
+        assert_eq!(semgrep.options.timeout(), Some(Duration::from_secs(600)));
+        assert!(semgrep.options.extra_args.is_empty());
+    }
 
-#[derive(Debug, Default)]
-pub struct Uname {}
+    #[test]
+    fn test_config_skips_unknown_tool_objects() {
+        let config: AmplifyConfigResponse = serde_json::from_str(
+            r#"{"tools":[{"name":"TRIVY","options":{"severity":"HIGH"}},"UNAME"],"merge_comments_enabled":false,"merge_approvals_enabled":false,"deleted":false}"#,
+        )
+        .unwrap();
+        assert_eq!(config.tools, vec![ToolConfig::from(Tools::Uname)]);
+    }
 
-impl Opengrep {
-    async fn install_rules(&self) -> Result<()> {
-        let body = reqwest::get(OPENGREP_RULES_URI)
-            .await
-            .wrap_err("Failed to fetch Amplify ruleset for Opengrep.")?
-            .bytes()
-            .await?;
-        let mut rules_file = File::create("/ruleset.json")?;
-        io::copy(&mut body.as_ref(), &mut rules_file)?;
-        Ok(())
-    }
-}
-
-impl ToolActions for Opengrep {
-    async fn setup(&self) -> Result<()> {
-        println!("::group::opengrep install");
-        let binary_url = format!(
-            "https://github.com/{repository}/releases/download/v{version}/{binary_name}",
-            repository = "opengrep/opengrep",
-            version = OPENGREP_VERSION,
-            binary_name = "opengrep_musllinux_x86"
+    #[test]
+    fn test_config_falls_back_to_default_options_it_does_not_understand() {
+        let config: AmplifyConfigResponse = serde_json::from_str(
+            r#"{"tools":[{"name":"TRIVY","options":{"timeout":"5m"}},{"name":"SEMGREP","options":{"timeout":"5m"}},{"name":"UNAME","options":null}],"merge_comments_enabled":false,"merge_approvals_enabled":false,"deleted":false}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.tools,
+            vec![
+                ToolConfig::from(Tools::Semgrep),
+                ToolConfig::from(Tools::Uname)
+            ]
         );
-        println!("Fetching Opengrep binary from {binary_url}.");
-        let opengrep_binary = reqwest::get(binary_url)
-            .await
-            .wrap_err("Failed to fetch Opengrep binary.")?
-            .bytes()
-            .await?;
-        println!("Verifying Opengrep binary checksum.");
-        let mut hasher = Sha256::new();
-        hasher.update(&opengrep_binary);
-        let hash = hasher.finalize();
-        if hash[..] != OPENGREP_CHECKSUM[..] {
-            return Err(eyre!(
-                "Opengrep binary failed checksum verification. Expected {}, got {}.",
-                const_hex::display(&OPENGREP_CHECKSUM),
-                const_hex::display(&hash)
-            ));
-        }
-        println!("Creating /usr/bin/opengrep.");
-        let mut binary_file = File::create("/usr/bin/opengrep")?;
-        io::copy(&mut opengrep_binary.as_ref(), &mut binary_file)?;
-
-        println!("Completed opengrep installation.");
-        println!("::endgroup::");
-        Ok(())
-    }
-
-    async fn launch(&self) -> Result<(ArtifactType, String)> {
-        // TODO: Split out command execution grouped output into helper functions
-        println!("::group::opengrep ci (scan job)");
-        let search_paths: String = env::var("PATH").expect("Couldn't identify PATH.");
-        self.install_rules().await?;
-        let opengrep_scan = Command::new("/usr/bin/opengrep")
-            // When public-api supports SARIF artifact ingestion, just change --json to --sarif here and update the return type
-            .args(["ci", "--json", "--oss-only", "--taint-intrafile"])
-            .env("PATH", format!("{search_paths}:/opengrep/bin"))
-            .env("SEMGREP_RULES", "/ruleset.json")
-            .env("SEMGREP_IN_DOCKER", "1")
-            .env("SEMGREP_USER_AGENT_APPEND", "Docker")
-            .stdout(Stdio::piped())
-            .spawn()
-            .expect("Failed to start Opengrep scan.");
-        println!("Started Opengrep scan: {opengrep_scan:?}");
-
-        let result = opengrep_scan.wait_with_output().await?;
-        println!("Finished Opengrep scan.");
-
-        let mut success = false;
-        match result.status.code() {
-            Some(code) => {
-                // Per Semgrep documentation (https://semgrep.dev/docs/cli-reference#exit-codes):
-                //   Semgrep can finish with the following exit codes:
-                //     0: Semgrep ran successfully and found no errors (or did find errors, but the --error flag is not
-                //        being used).
-                //     1: Semgrep ran successfully and found issues in your code (while using the --error flag).
-                // so we can treat 1 as success as well since we get a scan result.
-                if code == 0 || code == 1 {
-                    success = true;
-                } else {
-                    println!("Exited with non-successful exit code: {code}");
-                }
-            }
-            None => println!("Scan was prematurely terminated by an external signal."),
-        }
-        println!("::endgroup::");
-        if !success {
-            return Err(eyre!("Opengrep scan did not complete successfully."));
-        }
-        match String::from_utf8(result.stdout).context("Failed to read stdout from Opengrep.") {
-            Ok(out) => Ok((ArtifactType::Json, out)),
-            Err(e) => Err(e),
-        }
     }
-}
 
-impl ToolActions for Uname {
-    async fn setup(&self) -> Result<()> {
-        println!("Attempted setup function for uname.");
-        Ok(())
+    #[test]
+    fn test_deleted_config_needs_no_tools() {
+        let deleted: AmplifyConfigResponse = serde_json::from_str(
+            r#"{"tools":[],"merge_comments_enabled":false,"merge_approvals_enabled":false,"deleted":true}"#,
+        )
+        .unwrap();
+        assert!(deleted.validate().is_ok());
+
+        let empty: AmplifyConfigResponse = serde_json::from_str(
+            r#"{"tools":["TRIVY"],"merge_comments_enabled":false,"merge_approvals_enabled":false,"deleted":false}"#,
+        )
+        .unwrap();
+        assert!(empty.validate().is_err());
+    }
+
+    #[test]
+    fn test_tool_names_round_trip_through_serde() {
+        for tool in Tools::ALL {
+            let json = serde_json::to_string(&tool).unwrap();
+            assert_eq!(json, format!("\"{}\"", tool.as_str()));
+        }
     }
 
-    async fn launch(&self) -> Result<(ArtifactType, String)> {
-        let uname = Command::new("uname").args(["-a"]).spawn()?;
-        println!("Pushed off request for uname.");
-        uname.wait_with_output().await?;
-        println!("Finished running uname.");
-        Ok((ArtifactType::Json, "".to_string()))
+    #[test]
+    fn test_supported_headers() {
+        assert_eq!(supported_tools_header(), "SEMGREP,UNAME");
+        assert_eq!(supported_artifacts_header(), "application/json");
     }
 }
@@ -0,0 +1,235 @@
+//! Support for `.amplifyignore` files.
+//!
+//! An `.amplifyignore` file lists paths that should never be scanned or
+//! counted, using the same syntax and semantics as a `.gitignore` file,
+//! including negated patterns and files in subdirectories that apply to
+//! the paths below them.
+//!
+//! Everything the runner walks itself, like line counting and project
+//! discovery, goes through [`walker`], which applies the files as it goes.
+//! Tools walk the repository on their own, so findings they report for
+//! ignored paths are dropped afterwards with [`AmplifyIgnore::is_ignored`].
+//! The top-level file's patterns are also passed to tools as excludes so
+//! that ignored paths aren't scanned in the first place, but only when it
+//! has no negated patterns, since excludes can't re-include anything.
+
+use color_eyre::eyre::{Result, WrapErr};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::WalkBuilder;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Name of the ignore file, and location of the top-level one relative to
+/// the working directory.
+pub const AMPLIFYIGNORE_PATH: &str = ".amplifyignore";
+
+/// A walker over `paths` that skips everything `.amplifyignore` files
+/// ignore, or `None` if there are no paths.
+pub fn walker(paths: &[String]) -> Option<WalkBuilder> {
+    let (first, rest) = paths.split_first()?;
+    let mut walker = WalkBuilder::new(first);
+    for path in rest {
+        walker.add(path);
+    }
+    walker.add_custom_ignore_filename(AMPLIFYIGNORE_PATH);
+    Some(walker)
+}
+
+/// `path` relative to the working directory, without `.` components.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AmplifyIgnore {
+    /// Patterns that exclude paths, in the order they were listed.
+    excludes: Vec<String>,
+    /// Matchers for each ignore file, keyed by the directory it's in.
+    matchers: BTreeMap<PathBuf, Gitignore>,
+}
+
+impl AmplifyIgnore {
+    /// Load `.amplifyignore` from the working directory, and those in the
+    /// directories of `paths` and their parents. Missing files ignore
+    /// nothing.
+    pub fn load(paths: &[String]) -> Result<AmplifyIgnore> {
+        let mut ignore = match read(Path::new(""))? {
+            Some(contents) => Self::parse(&contents)?,
+            None => AmplifyIgnore::default(),
+        };
+        let parents = paths
+            .iter()
+            .flat_map(|path| Path::new(path).ancestors().skip(1))
+            .map(Path::to_path_buf);
+        let dirs = walker(paths)
+            .map(|walker| walker.build())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_some_and(|kind| kind.is_dir()))
+            .map(|entry| entry.path().to_path_buf());
+        for dir in parents.chain(dirs).map(|dir| normalize(&dir)) {
+            if dir.as_os_str().is_empty() || ignore.matchers.contains_key(&dir) {
+                continue;
+            }
+            if let Some(contents) = read(&dir)? {
+                let (_, matcher) = parse_file(&dir, &contents)?;
+                ignore.matchers.insert(dir, matcher);
+            }
+        }
+        Ok(ignore)
+    }
+
+    /// Parse the contents of the top-level ignore file.
+    pub fn parse(contents: &str) -> Result<AmplifyIgnore> {
+        let (excludes, matcher) = parse_file(Path::new(""), contents)?;
+        Ok(AmplifyIgnore {
+            excludes,
+            matchers: BTreeMap::from([(PathBuf::new(), matcher)]),
+        })
+    }
+
+    /// Patterns to pass to tools as excludes.
+    pub fn excludes(&self) -> &[String] {
+        &self.excludes
+    }
+
+    /// Whether there are no ignore files, so nothing is ignored.
+    pub fn is_empty(&self) -> bool {
+        self.matchers.is_empty()
+    }
+
+    /// Whether a file path, relative to the working directory, is ignored.
+    /// As with `.gitignore` files, the deepest file with a matching pattern
+    /// decides.
+    pub fn is_ignored(&self, path: impl AsRef<Path>) -> bool {
+        let path = normalize(path.as_ref());
+        for (dir, matcher) in self.matchers.iter().rev() {
+            let Ok(relative) = path.strip_prefix(dir) else {
+                continue;
+            };
+            let matched = matcher.matched_path_or_any_parents(relative, false);
+            if !matched.is_none() {
+                return matched.is_ignore();
+            }
+        }
+        false
+    }
+}
+
+/// Contents of the ignore file in `dir`, if there is one.
+fn read(dir: &Path) -> Result<Option<String>> {
+    let path = dir.join(AMPLIFYIGNORE_PATH);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    std::fs::read_to_string(&path)
+        .map(Some)
+        .wrap_err_with(|| format!("Failed to read {}.", path.display()))
+}
+
+/// Parse the ignore file in `dir` into the patterns that can be passed to
+/// tools as excludes, and a matcher for all of them.
+fn parse_file(dir: &Path, contents: &str) -> Result<(Vec<String>, Gitignore)> {
+    let path = dir.join(AMPLIFYIGNORE_PATH);
+    let mut builder = GitignoreBuilder::new(if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    });
+    let mut excludes = Vec::new();
+    let mut negated = false;
+    for (number, line) in contents.lines().enumerate() {
+        builder.add_line(None, line).wrap_err_with(|| {
+            format!(
+                "Invalid pattern on line {} of {}.",
+                number + 1,
+                path.display()
+            )
+        })?;
+        let pattern = line.trim();
+        if pattern.starts_with('!') {
+            negated = true;
+        } else if !pattern.is_empty() && !pattern.starts_with('#') {
+            excludes.push(pattern.to_owned());
+        }
+    }
+    // A negated pattern re-includes paths that an earlier exclude would
+    // keep tools from ever scanning.
+    if negated {
+        excludes.clear();
+    }
+    let matcher = builder
+        .build()
+        .wrap_err_with(|| format!("Failed to build matcher for {}.", path.display()))?;
+    Ok((excludes, matcher))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_support::TempDir;
+
+    #[test]
+    fn test_empty_file_ignores_nothing() {
+        assert!(AmplifyIgnore::default().is_empty());
+        let ignore = AmplifyIgnore::parse("").unwrap();
+        assert!(ignore.excludes().is_empty());
+        assert!(!ignore.is_ignored("src/main.rs"));
+    }
+
+    #[test]
+    fn test_comments_and_blank_lines_are_skipped() {
+        let ignore = AmplifyIgnore::parse("# vendored code\n\nvendor/\n").unwrap();
+        assert_eq!(ignore.excludes(), ["vendor/"]);
+    }
+
+    #[test]
+    fn test_directory_patterns_ignore_everything_below() {
+        let ignore = AmplifyIgnore::parse("vendor/\n").unwrap();
+        assert!(ignore.is_ignored("vendor/lib/a.js"));
+        assert!(ignore.is_ignored("./vendor/lib/a.js"));
+        assert!(!ignore.is_ignored("src/vendor.js"));
+    }
+
+    #[test]
+    fn test_file_patterns_match_in_any_directory() {
+        let ignore = AmplifyIgnore::parse("*.min.js\n").unwrap();
+        assert!(ignore.is_ignored("app.min.js"));
+        assert!(ignore.is_ignored("static/js/app.min.js"));
+        assert!(!ignore.is_ignored("static/js/app.js"));
+    }
+
+    #[test]
+    fn test_negated_patterns_disable_excludes() {
+        let ignore = AmplifyIgnore::parse("generated/\n!generated/keep.rs\n").unwrap();
+        // Excluding `generated/` would keep tools from scanning `keep.rs`.
+        assert!(ignore.excludes().is_empty());
+        assert!(ignore.is_ignored("generated/out.rs"));
+        assert!(!ignore.is_ignored("generated/keep.rs"));
+    }
+
+    #[test]
+    fn test_nested_files_apply_below_their_directory() {
+        let root = TempDir::new("amplifyignore-nested");
+        std::fs::write(root.join(AMPLIFYIGNORE_PATH), "*.min.js\n").unwrap();
+        std::fs::create_dir_all(root.join("web/dist")).unwrap();
+        std::fs::write(
+            root.join("web").join(AMPLIFYIGNORE_PATH),
+            "dist/\n!app.min.js\n",
+        )
+        .unwrap();
+        let paths = [root.to_string_lossy().into_owned()];
+
+        let ignore = AmplifyIgnore::load(&paths).unwrap();
+
+        let path = |relative: &str| root.join(relative);
+        assert!(ignore.is_ignored(path("web/dist/a.js")));
+        assert!(ignore.is_ignored(path("lib.min.js")));
+        // The deeper file re-includes what the top-level one ignores.
+        assert!(!ignore.is_ignored(path("web/app.min.js")));
+        assert!(!ignore.is_ignored(path("dist/a.js")));
+    }
+}
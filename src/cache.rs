@@ -0,0 +1,272 @@
+//! Cache of downloaded tool binaries and rules, shared across runs.
+//!
+//! Entries are stored as `<root>/<tool>/<version>/<checksum>/<file>`, so a
+//! cached file can only be reused for the exact release it was verified
+//! against. The layout doesn't depend on the repository or the machine,
+//! which lets CI systems save and restore the whole directory, e.g. with
+//! `actions/cache` or a GitLab `cache:paths` entry.
+//!
+//! Using a version marks its directory as recently used, so that pruning
+//! doesn't remove versions that only some runs use, like rulesets that a
+//! repository pins.
+
+use color_eyre::eyre::{Result, WrapErr};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const ENV_CACHE_DIR: &str = "AMPLIFY_CACHE_DIR";
+
+/// Name of the runner's directory inside the user's cache directory.
+const CACHE_DIR_NAME: &str = "amplify-runner";
+
+/// How long a version that the runner doesn't always use is kept after it
+/// was last used.
+pub const PRUNE_UNUSED_FOR: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolCache {
+    root: PathBuf,
+}
+
+impl Default for ToolCache {
+    fn default() -> Self {
+        ToolCache::resolve(None)
+    }
+}
+
+impl ToolCache {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        ToolCache { root: root.into() }
+    }
+
+    /// Pick the cache directory from `--cache-dir`, then `AMPLIFY_CACHE_DIR`,
+    /// then the XDG cache directory.
+    pub fn resolve(cli: Option<PathBuf>) -> Self {
+        let root = cli
+            .or_else(|| env_path(ENV_CACHE_DIR))
+            .unwrap_or_else(|| cache_home().join(CACHE_DIR_NAME));
+        ToolCache::new(root)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Directory for one version of a tool.
+    pub fn version_dir(&self, tool: &str, version: &str) -> PathBuf {
+        self.root.join(tool).join(version)
+    }
+
+    fn path(&self, tool: &str, version: &str, checksum: &[u8; 32], name: &str) -> PathBuf {
+        self.version_dir(tool, version)
+            .join(const_hex::encode(checksum))
+            .join(name)
+    }
+
+    /// A cached file, if there is one and it still matches `checksum`.
+    pub fn get(
+        &self,
+        tool: &str,
+        version: &str,
+        checksum: &[u8; 32],
+        name: &str,
+    ) -> Option<Vec<u8>> {
+        let path = self.path(tool, version, checksum, name);
+        let contents = std::fs::read(&path).ok()?;
+        if Sha256::digest(&contents)[..] == checksum[..] {
+            touch(&self.version_dir(tool, version));
+            Some(contents)
+        } else {
+            // Corrupted or tampered with, so it's downloaded again.
+            let _ = std::fs::remove_file(&path);
+            None
+        }
+    }
+
+    /// Store a file that has been verified against `checksum`.
+    pub fn put(
+        &self,
+        tool: &str,
+        version: &str,
+        checksum: &[u8; 32],
+        name: &str,
+        contents: &[u8],
+    ) -> Result<()> {
+        write_atomically(&self.path(tool, version, checksum, name), contents)?;
+        touch(&self.version_dir(tool, version));
+        Ok(())
+    }
+
+    /// Remove every tool version except the ones in `keep` and the ones used
+    /// within `unused_for`, returning the directories that were removed.
+    pub fn prune(&self, keep: &[(&str, &str)], unused_for: Duration) -> Result<Vec<PathBuf>> {
+        let mut removed = Vec::new();
+        let Ok(tools) = std::fs::read_dir(&self.root) else {
+            return Ok(removed);
+        };
+        for tool in tools.filter_map(|entry| entry.ok()) {
+            let tool_name = tool.file_name().to_string_lossy().into_owned();
+            let versions = std::fs::read_dir(tool.path())
+                .wrap_err_with(|| format!("Failed to read {}.", tool.path().display()))?;
+            for version in versions.filter_map(|entry| entry.ok()) {
+                let version_name = version.file_name().to_string_lossy().into_owned();
+                let recently_used = version
+                    .metadata()
+                    .and_then(|metadata| metadata.modified())
+                    .is_ok_and(|modified| {
+                        // A time in the future counts as just now.
+                        modified.elapsed().map_or(true, |age| age < unused_for)
+                    });
+                if recently_used || keep.contains(&(tool_name.as_str(), version_name.as_str())) {
+                    continue;
+                }
+                std::fs::remove_dir_all(version.path())
+                    .wrap_err_with(|| format!("Failed to remove {}.", version.path().display()))?;
+                removed.push(version.path());
+            }
+            // Only succeeds once every version of the tool is gone.
+            let _ = std::fs::remove_dir(tool.path());
+        }
+        Ok(removed)
+    }
+}
+
+/// Mark a directory as used just now. Failing to only means it may be
+/// pruned sooner.
+fn touch(dir: &Path) {
+    if let Ok(dir) = std::fs::File::open(dir) {
+        let _ = dir.set_modified(SystemTime::now());
+    }
+}
+
+fn env_path(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+/// The user's cache directory per the XDG base directory spec, or the
+/// temporary directory for users without a home directory.
+fn cache_home() -> PathBuf {
+    env_path("XDG_CACHE_HOME")
+        .or_else(|| env_path("HOME").map(|home| home.join(".cache")))
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+/// Write `contents` to `path` through a temporary file, so that a run that
+/// is interrupted, or a concurrent one, never sees a partial file.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(dir)
+        .wrap_err_with(|| format!("Failed to create {}.", dir.display()))?;
+    let temp = path.with_extension(format!("tmp{}", std::process::id()));
+    std::fs::write(&temp, contents)
+        .wrap_err_with(|| format!("Failed to write {}.", temp.display()))?;
+    std::fs::rename(&temp, path)
+        .wrap_err_with(|| format!("Failed to move {} into place.", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_support::TempDir;
+
+    #[test]
+    fn test_get_only_returns_verified_files() {
+        let dir = TempDir::new("cache");
+        let cache = ToolCache::new(dir.to_path_buf());
+        let contents = b"binary".to_vec();
+        let checksum: [u8; 32] = Sha256::digest(&contents).into();
+
+        assert_eq!(cache.get("opengrep", "1.0.0", &checksum, "opengrep"), None);
+        cache
+            .put("opengrep", "1.0.0", &checksum, "opengrep", &contents)
+            .unwrap();
+        assert_eq!(
+            cache.get("opengrep", "1.0.0", &checksum, "opengrep"),
+            Some(contents)
+        );
+        assert_eq!(cache.get("opengrep", "1.0.0", &[0; 32], "opengrep"), None);
+
+        std::fs::write(
+            cache.path("opengrep", "1.0.0", &checksum, "opengrep"),
+            "tampered",
+        )
+        .unwrap();
+        assert_eq!(cache.get("opengrep", "1.0.0", &checksum, "opengrep"), None);
+    }
+
+    #[test]
+    fn test_prune_keeps_current_versions() {
+        let dir = TempDir::new("cache");
+        let cache = ToolCache::new(dir.to_path_buf());
+        for (tool, version) in [
+            ("opengrep", "1.0.0"),
+            ("opengrep", "2.0.0"),
+            ("old", "1.0.0"),
+        ] {
+            cache.put(tool, version, &[0; 32], "file", b"").unwrap();
+        }
+
+        let mut removed = cache
+            .prune(&[("opengrep", "2.0.0")], Duration::ZERO)
+            .unwrap();
+        removed.sort();
+
+        assert_eq!(
+            removed,
+            vec![
+                cache.version_dir("old", "1.0.0"),
+                cache.version_dir("opengrep", "1.0.0")
+            ]
+        );
+        assert!(cache.version_dir("opengrep", "2.0.0").exists());
+        assert!(!cache.root().join("old").exists());
+    }
+
+    #[test]
+    fn test_prune_keeps_recently_used_versions() {
+        let dir = TempDir::new("cache");
+        let cache = ToolCache::new(dir.to_path_buf());
+        let checksum: [u8; 32] = Sha256::digest(b"rules").into();
+        for version in ["v1", "v2"] {
+            cache
+                .put("opengrep-rules", version, &checksum, "rules.json", b"rules")
+                .unwrap();
+        }
+        let long_ago = SystemTime::now() - 2 * PRUNE_UNUSED_FOR;
+        for version in ["v1", "v2"] {
+            let version_dir = std::fs::File::open(cache.version_dir("opengrep-rules", version));
+            version_dir.unwrap().set_modified(long_ago).unwrap();
+        }
+        // Used again, like a ruleset that a repository pins.
+        assert!(cache
+            .get("opengrep-rules", "v2", &checksum, "rules.json")
+            .is_some());
+
+        let removed = cache.prune(&[], PRUNE_UNUSED_FOR).unwrap();
+
+        assert_eq!(removed, vec![cache.version_dir("opengrep-rules", "v1")]);
+        assert!(cache.version_dir("opengrep-rules", "v2").exists());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_precedence() {
+        let _lock = crate::common::test_support::ENV_MUTEX.lock().await;
+        std::env::set_var(ENV_CACHE_DIR, "/env/cache");
+        assert_eq!(
+            ToolCache::resolve(Some("/cli/cache".into())).root(),
+            Path::new("/cli/cache")
+        );
+        assert_eq!(ToolCache::resolve(None).root(), Path::new("/env/cache"));
+
+        std::env::remove_var(ENV_CACHE_DIR);
+        std::env::set_var("XDG_CACHE_HOME", "/xdg");
+        assert_eq!(
+            ToolCache::resolve(None).root(),
+            Path::new("/xdg/amplify-runner")
+        );
+        std::env::remove_var("XDG_CACHE_HOME");
+    }
+}
@@ -1,13 +1,41 @@
 use bpaf::*;
 use color_eyre::eyre::Result;
+use std::path::PathBuf;
 use std::str::FromStr;
 
+use crate::scheduler::FailurePolicy;
+use crate::tools::opengrep::report::Severity;
+
 const DEFAULT_AMPLIFY_ENDPOINT: &str = "https://api.amplify.security";
 
 #[derive(Debug, Clone)]
 pub struct RunnerArgs {
     pub ci: Option<ExecutionEnvironment>,
     pub endpoint: Option<String>,
+    pub exclude: Vec<String>,
+    pub rules: Vec<String>,
+    pub paths: Vec<String>,
+    pub monorepo: bool,
+    pub jobs: Option<usize>,
+    pub failure_policy: Option<FailurePolicy>,
+    pub run_timeout: Option<u64>,
+    pub max_artifact_mb: Option<u64>,
+    pub fail_on: Option<Severity>,
+    pub max_annotations: Option<usize>,
+    pub reports: Vec<ReportOutput>,
+    pub cache_dir: Option<PathBuf>,
+    pub work_dir: Option<PathBuf>,
+    pub baseline_commit: Option<String>,
+    pub full_scan: bool,
+    pub command: Option<RunnerCommand>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunnerCommand {
+    /// Print the effective configuration instead of running any scans.
+    ConfigShow,
+    /// Remove cached tools that this runner version no longer uses.
+    CachePrune,
 }
 
 pub fn init() -> RunnerArgs {
@@ -21,9 +49,119 @@ pub fn init() -> RunnerArgs {
         .argument::<ExecutionEnvironment>("PLATFORM")
         .optional();
 
-    let parser = construct!(RunnerArgs { ci, endpoint })
+    let exclude = long("exclude")
+        .help("Path or glob pattern to exclude from every scan. May be repeated.")
+        .argument::<String>("PATTERN")
+        .many();
+
+    let rules = long("rules")
+        .help("Additional rule pack or rule file to scan with. May be repeated.")
+        .argument::<String>("RULES")
+        .many();
+
+    let paths = long("path")
+        .help("Path to scan, relative to the working directory. May be repeated. Defaults to the whole working directory.")
+        .argument::<String>("PATH")
+        .many();
+
+    let monorepo = long("monorepo")
+        .help("Scan and report each project in the repository separately.")
+        .switch();
+
+    let jobs = long("jobs")
+        .help("Maximum number of tools to run at the same time. Defaults to the number of CPUs.")
+        .argument::<usize>("N")
+        .optional();
+
+    let failure_policy = long("failure-policy")
+        .help("What to do when a tool fails: fail-fast (the default), continue, or best-effort.")
+        .argument::<FailurePolicy>("POLICY")
+        .optional();
+
+    let run_timeout = long("run-timeout")
+        .help("Maximum time in seconds for the whole run. Tools that are still running when it's up are stopped.")
+        .argument::<u64>("SECS")
+        .optional();
+
+    let max_artifact_mb = long("max-artifact-mb")
+        .help("Largest report in megabytes that a tool may produce. Defaults to 100.")
+        .argument::<u64>("MB")
+        .optional();
+
+    let fail_on = long("fail-on")
+        .help("Fail the run when there are findings of this severity or higher: low, medium, high or critical. Results are uploaded either way.")
+        .argument::<Severity>("SEVERITY")
+        .optional();
+
+    let max_annotations = long("max-annotations")
+        .help("Most findings to annotate the code with on GitHub Actions, or 0 for none. Defaults to 50.")
+        .argument::<usize>("N")
+        .optional();
+
+    let reports = long("report")
+        .help("Also write the findings as FORMAT to PATH, e.g. gitlab-sast=gl-sast-report.json. May be repeated.")
+        .argument::<ReportOutput>("FORMAT=PATH")
+        .many();
+
+    let cache_dir = long("cache-dir")
+        .help("Directory to cache downloaded tools in between runs. Defaults to $XDG_CACHE_HOME/amplify-runner.")
+        .argument::<PathBuf>("DIR")
+        .optional();
+
+    let work_dir = long("work-dir")
+        .help("Directory to install tools and write intermediate files in. Defaults to a temporary directory that is removed after the run.")
+        .argument::<PathBuf>("DIR")
+        .optional();
+
+    let baseline_commit = long("baseline-commit")
+        .help("Only report findings introduced since this commit. Detected automatically on pull and merge requests.")
+        .argument::<String>("COMMIT")
+        .optional();
+
+    let full_scan = long("full-scan")
+        .help("Report every finding, even on pull and merge requests.")
+        .switch();
+
+    let config_show = pure(RunnerCommand::ConfigShow)
         .to_options()
-        .descr("Amplify Runner");
+        .descr("Print the effective configuration and where each value came from.")
+        .command("show");
+    let config = construct!([config_show])
+        .to_options()
+        .descr("Inspect the runner configuration.")
+        .command("config");
+    let cache_prune = pure(RunnerCommand::CachePrune)
+        .to_options()
+        .descr("Remove cached tools that this runner version no longer uses.")
+        .command("prune");
+    let cache = construct!([cache_prune])
+        .to_options()
+        .descr("Manage the tool cache.")
+        .command("cache");
+    let command = construct!([config, cache]).optional();
+
+    let parser = construct!(RunnerArgs {
+        ci,
+        endpoint,
+        exclude,
+        rules,
+        paths,
+        monorepo,
+        jobs,
+        failure_policy,
+        run_timeout,
+        max_artifact_mb,
+        fail_on,
+        max_annotations,
+        reports,
+        cache_dir,
+        work_dir,
+        baseline_commit,
+        full_scan,
+        command
+    })
+    .to_options()
+    .descr("Amplify Runner");
 
     let mut args = parser.run();
 
@@ -72,6 +210,44 @@ impl FromStr for ExecutionEnvironment {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// GitLab's SAST report, shown in merge request widgets.
+    GitlabSast,
+}
+
+/// A report of the findings to write, requested with `--report`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportOutput {
+    pub format: ReportFormat,
+    pub path: PathBuf,
+}
+
+impl FromStr for ReportOutput {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (format, path) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Expected FORMAT=PATH, got `{s}`."))?;
+        if path.is_empty() {
+            return Err(format!("Missing path for the `{format}` report."));
+        }
+        let format = match format {
+            "gitlab-sast" => ReportFormat::GitlabSast,
+            _ => {
+                return Err(format!(
+                    "Unknown report format `{format}`, expected gitlab-sast."
+                ))
+            }
+        };
+        Ok(ReportOutput {
+            format,
+            path: PathBuf::from(path),
+        })
+    }
+}
+
 fn identify_ci_from_environment() -> Option<ExecutionEnvironment> {
     // https://docs.github.com/en/actions/learn-github-actions/variables
     if std::env::var("GITHUB_ACTIONS").unwrap_or_default() == "true" {
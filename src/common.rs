@@ -1,22 +1,53 @@
 use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
 use reqwest_retry::{
     default_on_request_failure, default_on_request_success, policies::ExponentialBackoff,
-    RetryTransientMiddleware, Retryable, RetryableStrategy,
+    RetryDecision, RetryPolicy, RetryTransientMiddleware, Retryable, RetryableStrategy,
 };
-use std::time::Duration;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
 
-pub fn new_http_client() -> ClientWithMiddleware {
+fn retry_policy() -> impl RetryPolicy + Send + Sync + 'static {
     // Retry on failure for up to 15 seconds
-    let retry_policy =
-        ExponentialBackoff::builder().build_with_total_retry_duration(Duration::from_secs(15));
+    ExponentialBackoff::builder().build_with_total_retry_duration(Duration::from_secs(15))
+}
+
+pub fn new_http_client() -> ClientWithMiddleware {
     ClientBuilder::new(reqwest::Client::new())
         .with(RetryTransientMiddleware::new_with_policy_and_strategy(
-            retry_policy,
+            retry_policy(),
             DefaultRetryStrategyWith401,
         ))
         .build()
 }
 
+/// Send the request that `request` builds, with the same retries as
+/// [`new_http_client`]. The retry middleware can't resend a streaming body,
+/// so every attempt here builds the request, and its body, again.
+pub async fn send_with_retry(
+    mut request: impl FnMut() -> color_eyre::eyre::Result<reqwest::RequestBuilder>,
+) -> color_eyre::eyre::Result<reqwest::Response> {
+    let policy = retry_policy();
+    let started = SystemTime::now();
+    let mut retries = 0;
+    loop {
+        let result = request()?
+            .send()
+            .await
+            .map_err(reqwest_middleware::Error::from);
+        if let Some(Retryable::Transient) = DefaultRetryStrategyWith401.handle(&result) {
+            if let RetryDecision::Retry { execute_after } = policy.should_retry(started, retries) {
+                let delay = execute_after
+                    .duration_since(SystemTime::now())
+                    .unwrap_or_default();
+                tokio::time::sleep(delay).await;
+                retries += 1;
+                continue;
+            }
+        }
+        return Ok(result?);
+    }
+}
+
 struct DefaultRetryStrategyWith401;
 impl RetryableStrategy for DefaultRetryStrategyWith401 {
     fn handle(&self, res: &reqwest_middleware::Result<reqwest::Response>) -> Option<Retryable> {
@@ -28,9 +59,102 @@ impl RetryableStrategy for DefaultRetryStrategyWith401 {
     }
 }
 
+/// Output from one tool, buffered so that tools running at the same time
+/// don't interleave their lines in the CI log.
+#[derive(Debug, Default)]
+pub struct ToolLog {
+    lines: Mutex<Vec<String>>,
+}
+
+impl ToolLog {
+    pub fn println(&self, line: impl Into<String>) {
+        self.lines
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(line.into());
+    }
+
+    /// Print the buffered lines as one collapsible group titled `title`.
+    pub fn flush(&self, title: &str) {
+        let lines = std::mem::take(&mut *self.lines.lock().unwrap_or_else(PoisonError::into_inner));
+        // Hold stdout so that nothing else is printed inside the group.
+        let _stdout = std::io::stdout().lock();
+        println!("::group::{title}");
+        for line in lines {
+            println!("{line}");
+        }
+        println!("::endgroup::");
+    }
+
+    #[cfg(test)]
+    pub fn lines(&self) -> Vec<String> {
+        self.lines
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
 /// Shared utilities for tests across the crate.
 #[cfg(test)]
 pub(crate) mod test_support {
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::scheduler::{Outcome, RunSummary, Status};
+    use crate::tools::opengrep::report::Report;
+
     /// Async mutex used by every test that reads or writes env vars.
     pub(crate) static ENV_MUTEX: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    /// An empty scratch directory, removed with everything in it on drop.
+    /// Every one is new, so tests running in parallel never share one.
+    pub(crate) struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub(crate) fn new(name: &str) -> TempDir {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "amplify-{name}-{}-{}",
+                std::process::id(),
+                COUNT.fetch_add(1, Ordering::Relaxed)
+            ));
+            // Left over from an earlier run that reused the process ID.
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl std::ops::Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A run of Opengrep for each of `statuses`, each with the findings in
+    /// the Opengrep JSON `report`, if any.
+    pub(crate) fn summary(statuses: &[Status], report: Option<&str>) -> RunSummary {
+        RunSummary {
+            outcomes: statuses
+                .iter()
+                .map(|status| Outcome {
+                    tool: "opengrep",
+                    project_path: None,
+                    status: status.clone(),
+                    findings: report.map(|report| Report::parse(report).unwrap()),
+                    duration: None,
+                })
+                .collect(),
+            code_lines: 0,
+        }
+    }
 }
@@ -0,0 +1,986 @@
+//! Effective runner configuration.
+//!
+//! Settings are merged from several layers. When a value is set in more than
+//! one layer, the one with the highest precedence wins:
+//!
+//! 1. **CLI** – flags passed to the runner, e.g. `--exclude` or `--path`.
+//! 2. **Environment** – `AMPLIFY_*` variables, e.g. `AMPLIFY_EXCLUDE` or
+//!    `AMPLIFY_SCAN_PATHS`.
+//! 3. **Repository file** – `.amplify/runner.toml` in the working directory.
+//! 4. **Remote** – the project configuration returned by Amplify's API.
+//!
+//! List values from the environment are comma-separated.
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::amplify::{AmplifyConfigResponse, ProjectSettings, ToolConfig, ToolOptions, Tools};
+use crate::cli::RunnerArgs;
+use crate::github::DEFAULT_MAX_ANNOTATIONS;
+use crate::monorepo::{discover_projects, DEFAULT_PROJECT_MARKERS};
+use crate::scheduler::FailurePolicy;
+use crate::tools::opengrep::report::Severity;
+
+/// Location of the repository-level config file, relative to the working
+/// directory.
+pub const REPO_CONFIG_PATH: &str = ".amplify/runner.toml";
+
+const ENV_EXCLUDE: &str = "AMPLIFY_EXCLUDE";
+const ENV_RULES: &str = "AMPLIFY_RULES";
+const ENV_SCAN_PATHS: &str = "AMPLIFY_SCAN_PATHS";
+const ENV_MONOREPO: &str = "AMPLIFY_MONOREPO";
+const ENV_JOBS: &str = "AMPLIFY_JOBS";
+const ENV_FAILURE_POLICY: &str = "AMPLIFY_FAILURE_POLICY";
+const ENV_RUN_TIMEOUT: &str = "AMPLIFY_RUN_TIMEOUT";
+const ENV_MAX_ARTIFACT_MB: &str = "AMPLIFY_MAX_ARTIFACT_MB";
+const ENV_FAIL_ON: &str = "AMPLIFY_FAIL_ON";
+const ENV_MAX_ANNOTATIONS: &str = "AMPLIFY_MAX_ANNOTATIONS";
+
+/// Largest report, in megabytes, that a tool may produce by default.
+const DEFAULT_MAX_ARTIFACT_MB: u64 = 100;
+
+/// The layer that a configuration value came from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+    Default,
+    Remote,
+    RepoFile,
+    Env,
+    Cli,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::Remote => write!(f, "remote"),
+            Source::RepoFile => write!(f, "{REPO_CONFIG_PATH}"),
+            Source::Env => write!(f, "environment"),
+            Source::Cli => write!(f, "command line"),
+        }
+    }
+}
+
+/// A configuration value along with the layer it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Setting<T> {
+    fn new(value: T, source: Source) -> Self {
+        Setting { value, source }
+    }
+}
+
+/// Values that can be overridden from the CLI or the environment.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Overrides {
+    pub exclude: Option<Vec<String>>,
+    pub rules: Option<Vec<String>>,
+    pub paths: Option<Vec<String>>,
+    pub monorepo: Option<bool>,
+    pub jobs: Option<usize>,
+    pub failure_policy: Option<FailurePolicy>,
+    pub run_timeout: Option<u64>,
+    pub max_artifact_mb: Option<u64>,
+    pub fail_on: Option<Severity>,
+    pub max_annotations: Option<usize>,
+}
+
+impl Overrides {
+    pub fn from_args(args: &RunnerArgs) -> Self {
+        Overrides {
+            exclude: non_empty(args.exclude.clone()),
+            rules: non_empty(args.rules.clone()),
+            paths: non_empty(args.paths.clone()),
+            monorepo: args.monorepo.then_some(true),
+            jobs: args.jobs,
+            failure_policy: args.failure_policy,
+            run_timeout: args.run_timeout,
+            max_artifact_mb: args.max_artifact_mb,
+            fail_on: args.fail_on,
+            max_annotations: args.max_annotations,
+        }
+    }
+
+    /// Settings from environment variables. A variable that is set to a
+    /// value that doesn't parse is an error rather than ignored, so that a
+    /// typo can't quietly turn a setting off.
+    pub fn from_env() -> Result<Self> {
+        Ok(Overrides {
+            exclude: list_from_env(ENV_EXCLUDE),
+            rules: list_from_env(ENV_RULES),
+            paths: list_from_env(ENV_SCAN_PATHS),
+            monorepo: bool_from_env(ENV_MONOREPO)?,
+            jobs: parse_from_env(ENV_JOBS)?,
+            failure_policy: parse_from_env(ENV_FAILURE_POLICY)?,
+            run_timeout: parse_from_env(ENV_RUN_TIMEOUT)?,
+            max_artifact_mb: parse_from_env(ENV_MAX_ARTIFACT_MB)?,
+            fail_on: parse_from_env(ENV_FAIL_ON)?,
+            max_annotations: parse_from_env(ENV_MAX_ANNOTATIONS)?,
+        })
+    }
+}
+
+fn non_empty(values: Vec<String>) -> Option<Vec<String>> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values)
+    }
+}
+
+fn list_from_env(name: &str) -> Option<Vec<String>> {
+    let value = std::env::var(name).ok()?;
+    non_empty(
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_owned)
+            .collect(),
+    )
+}
+
+/// The trimmed value of the variable `name`, or `None` if it's unset or
+/// empty.
+fn var_from_env(name: &str) -> Option<String> {
+    let value = std::env::var(name).ok()?;
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_owned())
+}
+
+fn bool_from_env(name: &str) -> Result<Option<bool>> {
+    let Some(value) = var_from_env(name) else {
+        return Ok(None);
+    };
+    match value.as_str() {
+        "1" | "true" => Ok(Some(true)),
+        "0" | "false" => Ok(Some(false)),
+        _ => Err(eyre!(
+            "Invalid value `{value}` for {name}, expected true or false."
+        )),
+    }
+}
+
+fn parse_from_env<T>(name: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let Some(value) = var_from_env(name) else {
+        return Ok(None);
+    };
+    value
+        .parse()
+        .map(Some)
+        .map_err(|e| eyre!("Invalid value `{value}` for {name}: {e}"))
+}
+
+/// Deserialize an optional value with its `FromStr` implementation, so that
+/// the config file accepts the same spellings as the CLI.
+fn from_str_opt<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = String>,
+{
+    let value = String::deserialize(deserializer)?;
+    value.parse().map(Some).map_err(serde::de::Error::custom)
+}
+
+/// Contents of the repository-level config file.
+///
+/// ```toml
+/// exclude = ["vendor/", "**/*.min.js"]
+/// rules = ["rules/internal.yml"]
+/// paths = ["services/api"]
+/// monorepo = true
+/// projects = ["services/api", "services/web"]
+/// jobs = 2
+/// failure_policy = "continue"
+/// run_timeout = 3600
+/// max_artifact_mb = 200
+/// fail_on = "high"
+/// max_annotations = 20
+///
+/// [tools.semgrep]
+/// timeout = 900
+/// max_memory_mb = 4096
+/// rules_version = "v2025.01.0"
+///
+/// [tools.uname]
+/// enabled = false
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RepoConfig {
+    /// Paths or glob patterns excluded from every tool.
+    pub exclude: Option<Vec<String>>,
+    /// Additional rules run by every tool that supports them.
+    pub rules: Option<Vec<String>>,
+    /// Paths to scan, relative to the working directory.
+    pub paths: Option<Vec<String>>,
+    /// Scan and report each project in the repository separately.
+    pub monorepo: Option<bool>,
+    /// Project roots for monorepo mode. When unset, projects are discovered
+    /// by looking for `project_markers`.
+    pub projects: Option<Vec<String>>,
+    /// File names that mark a project root in monorepo mode.
+    pub project_markers: Option<Vec<String>>,
+    /// Maximum number of tools to run at the same time.
+    pub jobs: Option<usize>,
+    /// What to do when a tool fails: `fail-fast`, `continue` or
+    /// `best-effort`.
+    pub failure_policy: Option<FailurePolicy>,
+    /// Maximum time in seconds for the whole run. Tools that are still
+    /// running when it's up are stopped.
+    pub run_timeout: Option<u64>,
+    /// Largest report, in megabytes, that a tool may produce.
+    pub max_artifact_mb: Option<u64>,
+    /// Fail the run when there are findings of this severity or higher.
+    #[serde(deserialize_with = "from_str_opt")]
+    pub fail_on: Option<Severity>,
+    /// Most findings to annotate the code with on GitHub Actions, or 0 for
+    /// none.
+    pub max_annotations: Option<usize>,
+    /// Per-tool settings, keyed by tool name (case-insensitive).
+    pub tools: BTreeMap<String, RepoToolConfig>,
+}
+
+/// Per-tool settings in the repository-level config file. Any option that
+/// is set replaces the remote value for that option.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RepoToolConfig {
+    /// Run (`true`) or skip (`false`) the tool regardless of the remote
+    /// configuration.
+    pub enabled: Option<bool>,
+    pub rules: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+    pub timeout: Option<u64>,
+    pub max_memory_mb: Option<u64>,
+    pub max_cpu_seconds: Option<u64>,
+    pub extra_args: Option<Vec<String>>,
+    pub rules_version: Option<String>,
+    pub rules_sha256: Option<String>,
+}
+
+impl RepoToolConfig {
+    fn has_options(&self) -> bool {
+        self.rules.is_some()
+            || self.exclude.is_some()
+            || self.timeout.is_some()
+            || self.max_memory_mb.is_some()
+            || self.max_cpu_seconds.is_some()
+            || self.extra_args.is_some()
+            || self.rules_version.is_some()
+            || self.rules_sha256.is_some()
+    }
+
+    fn apply_to(&self, options: &mut ToolOptions) {
+        if let Some(rules) = &self.rules {
+            options.rules = rules.clone();
+        }
+        if let Some(exclude) = &self.exclude {
+            options.exclude = exclude.clone();
+        }
+        if let Some(timeout) = self.timeout {
+            options.timeout = Some(timeout);
+        }
+        if let Some(max_memory_mb) = self.max_memory_mb {
+            options.max_memory_mb = Some(max_memory_mb);
+        }
+        if let Some(max_cpu_seconds) = self.max_cpu_seconds {
+            options.max_cpu_seconds = Some(max_cpu_seconds);
+        }
+        if let Some(extra_args) = &self.extra_args {
+            options.extra_args = extra_args.clone();
+        }
+        if let Some(rules_version) = &self.rules_version {
+            options.rules_version = Some(rules_version.clone());
+        }
+        if let Some(rules_sha256) = &self.rules_sha256 {
+            options.rules_sha256 = Some(rules_sha256.clone());
+        }
+    }
+}
+
+impl RepoConfig {
+    /// Load the repository-level config file, if there is one.
+    pub fn load() -> Result<Option<RepoConfig>> {
+        Self::load_from(Path::new(REPO_CONFIG_PATH))
+    }
+
+    fn load_from(path: &Path) -> Result<Option<RepoConfig>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read {}.", path.display()))?;
+        let config = toml::from_str(&contents)
+            .wrap_err_with(|| format!("Failed to parse {}.", path.display()))?;
+        Ok(Some(config))
+    }
+}
+
+/// Pick the value from the layer with the highest precedence that sets it.
+fn pick<T>(cli: Option<T>, env: Option<T>, repo: Option<T>, default: T) -> Setting<T> {
+    cli.map(|value| Setting::new(value, Source::Cli))
+        .or_else(|| env.map(|value| Setting::new(value, Source::Env)))
+        .or_else(|| repo.map(|value| Setting::new(value, Source::RepoFile)))
+        .unwrap_or_else(|| Setting::new(default, Source::Default))
+}
+
+/// Run as many tools at once as there are CPUs, since most of them are
+/// CPU-bound.
+fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, |jobs| jobs.get())
+}
+
+/// The configuration for this run after merging every layer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EffectiveConfig {
+    pub tools: Vec<Setting<ToolConfig>>,
+    pub exclude: Setting<Vec<String>>,
+    pub rules: Setting<Vec<String>>,
+    pub paths: Setting<Vec<String>>,
+    pub monorepo: Setting<bool>,
+    pub projects: Setting<Vec<String>>,
+    pub project_markers: Setting<Vec<String>>,
+    pub jobs: Setting<usize>,
+    pub failure_policy: Setting<FailurePolicy>,
+    /// Maximum time in seconds for the whole run, or `None` for no limit.
+    pub run_timeout: Setting<Option<u64>>,
+    pub max_artifact_mb: Setting<u64>,
+    /// Lowest severity of findings that fails the run, or `None` to never
+    /// fail because of findings.
+    pub fail_on: Setting<Option<Severity>>,
+    pub max_annotations: Setting<usize>,
+    pub settings: ProjectSettings,
+    pub deleted: bool,
+    pub project_url: Option<String>,
+}
+
+impl EffectiveConfig {
+    /// Merge every configuration layer, from highest to lowest precedence.
+    pub fn merge(
+        cli: Overrides,
+        env: Overrides,
+        repo: Option<RepoConfig>,
+        remote: AmplifyConfigResponse,
+    ) -> Result<EffectiveConfig> {
+        let repo = repo.unwrap_or_default();
+
+        let exclude = pick(cli.exclude, env.exclude, repo.exclude, Vec::new());
+        let rules = pick(cli.rules, env.rules, repo.rules, Vec::new());
+        let paths = pick(cli.paths, env.paths, repo.paths, vec![".".to_owned()]);
+        for path in &paths.value {
+            if !Path::new(path).exists() {
+                return Err(eyre!(
+                    "Scan path `{path}` from the {} does not exist.",
+                    paths.source
+                ));
+            }
+        }
+
+        let monorepo = pick(cli.monorepo, env.monorepo, repo.monorepo, false);
+        let projects = pick(None, None, repo.projects, Vec::new());
+        // Projects are only scanned in monorepo mode.
+        if monorepo.value {
+            for project in &projects.value {
+                if !Path::new(project).is_dir() {
+                    return Err(eyre!(
+                        "Project `{project}` from the {} is not a directory.",
+                        projects.source
+                    ));
+                }
+            }
+        }
+        let project_markers = pick(
+            None,
+            None,
+            repo.project_markers,
+            DEFAULT_PROJECT_MARKERS
+                .iter()
+                .map(|marker| marker.to_string())
+                .collect(),
+        );
+
+        let jobs = pick(cli.jobs, env.jobs, repo.jobs, default_jobs());
+        if jobs.value == 0 {
+            return Err(eyre!("`jobs` from the {} must be at least 1.", jobs.source));
+        }
+
+        let failure_policy = pick(
+            cli.failure_policy,
+            env.failure_policy,
+            repo.failure_policy,
+            FailurePolicy::default(),
+        );
+
+        let run_timeout = pick(
+            cli.run_timeout.map(Some),
+            env.run_timeout.map(Some),
+            repo.run_timeout.map(Some),
+            None,
+        );
+        if run_timeout.value == Some(0) {
+            return Err(eyre!(
+                "`run_timeout` from the {} must be at least 1 second.",
+                run_timeout.source
+            ));
+        }
+
+        let max_artifact_mb = pick(
+            cli.max_artifact_mb,
+            env.max_artifact_mb,
+            repo.max_artifact_mb,
+            DEFAULT_MAX_ARTIFACT_MB,
+        );
+        if max_artifact_mb.value == 0 {
+            return Err(eyre!(
+                "`max_artifact_mb` from the {} must be at least 1.",
+                max_artifact_mb.source
+            ));
+        }
+
+        let fail_on = pick(
+            cli.fail_on.map(Some),
+            env.fail_on.map(Some),
+            repo.fail_on.map(Some),
+            None,
+        );
+
+        let max_annotations = pick(
+            cli.max_annotations,
+            env.max_annotations,
+            repo.max_annotations,
+            DEFAULT_MAX_ANNOTATIONS,
+        );
+
+        let mut repo_tools = BTreeMap::new();
+        for (name, tool) in repo.tools {
+            let tool_name = Tools::from_name(&name.to_uppercase())
+                .ok_or_else(|| eyre!("Unknown tool `{name}` in {REPO_CONFIG_PATH}."))?;
+            repo_tools.insert(tool_name, tool);
+        }
+
+        let settings = remote.settings();
+        let mut tools = Vec::new();
+        for mut tool in remote.tools {
+            let mut source = Source::Remote;
+            if let Some(repo_tool) = repo_tools.remove(&tool.name) {
+                if repo_tool.enabled == Some(false) {
+                    continue;
+                }
+                if repo_tool.has_options() {
+                    repo_tool.apply_to(&mut tool.options);
+                    source = Source::RepoFile;
+                }
+            }
+            tools.push(Setting::new(tool, source));
+        }
+        // Tools that are only enabled by the repository file.
+        for (name, repo_tool) in repo_tools {
+            if repo_tool.enabled == Some(true) {
+                let mut tool = ToolConfig::from(name);
+                repo_tool.apply_to(&mut tool.options);
+                tools.push(Setting::new(tool, Source::RepoFile));
+            }
+        }
+
+        Ok(EffectiveConfig {
+            tools,
+            exclude,
+            rules,
+            paths,
+            monorepo,
+            projects,
+            project_markers,
+            jobs,
+            failure_policy,
+            run_timeout,
+            max_artifact_mb,
+            fail_on,
+            max_annotations,
+            settings,
+            deleted: remote.deleted,
+            project_url: remote.project_url,
+        })
+    }
+
+    /// Project roots to scan separately in monorepo mode, or `None` when the
+    /// scan paths are reported as a single project.
+    pub fn project_roots(&self) -> Option<Vec<String>> {
+        if !self.monorepo.value {
+            return None;
+        }
+        if !self.projects.value.is_empty() {
+            return Some(self.projects.value.clone());
+        }
+        Some(discover_projects(
+            &self.paths.value,
+            &self.project_markers.value,
+        ))
+    }
+
+    /// Tools to run, with the global excludes and rules added to each tool's
+    /// own options.
+    pub fn tool_configs(&self) -> Vec<ToolConfig> {
+        self.tools
+            .iter()
+            .map(|tool| {
+                let mut tool = tool.value.clone();
+                tool.options
+                    .exclude
+                    .extend(self.exclude.value.iter().cloned());
+                tool.options.rules.extend(self.rules.value.iter().cloned());
+                tool
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for EffectiveConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "exclude = {:?}  # {}",
+            self.exclude.value, self.exclude.source
+        )?;
+        writeln!(f, "rules = {:?}  # {}", self.rules.value, self.rules.source)?;
+        writeln!(f, "paths = {:?}  # {}", self.paths.value, self.paths.source)?;
+        writeln!(
+            f,
+            "monorepo = {}  # {}",
+            self.monorepo.value, self.monorepo.source
+        )?;
+        writeln!(
+            f,
+            "projects = {:?}  # {}",
+            self.projects.value, self.projects.source
+        )?;
+        writeln!(
+            f,
+            "project_markers = {:?}  # {}",
+            self.project_markers.value, self.project_markers.source
+        )?;
+        writeln!(f, "jobs = {}  # {}", self.jobs.value, self.jobs.source)?;
+        writeln!(
+            f,
+            "failure_policy = \"{}\"  # {}",
+            self.failure_policy.value, self.failure_policy.source
+        )?;
+        if let Some(run_timeout) = self.run_timeout.value {
+            writeln!(
+                f,
+                "run_timeout = {run_timeout}  # {}",
+                self.run_timeout.source
+            )?;
+        }
+        writeln!(
+            f,
+            "max_artifact_mb = {}  # {}",
+            self.max_artifact_mb.value, self.max_artifact_mb.source
+        )?;
+        if let Some(fail_on) = self.fail_on.value {
+            writeln!(f, "fail_on = \"{fail_on}\"  # {}", self.fail_on.source)?;
+        }
+        writeln!(
+            f,
+            "max_annotations = {}  # {}",
+            self.max_annotations.value, self.max_annotations.source
+        )?;
+        if !self.settings.merge_comments_enabled {
+            writeln!(
+                f,
+                "# GitHub annotations are off: merge comments are disabled in Amplify."
+            )?;
+        }
+        writeln!(
+            f,
+            "merge_comments_enabled = {}  # {}",
+            self.settings.merge_comments_enabled,
+            Source::Remote
+        )?;
+        writeln!(
+            f,
+            "merge_approvals_enabled = {}  # {}",
+            self.settings.merge_approvals_enabled,
+            Source::Remote
+        )?;
+        writeln!(f, "deleted = {}  # {}", self.deleted, Source::Remote)?;
+        if let Some(project_url) = &self.project_url {
+            writeln!(f, "project_url = {project_url:?}  # {}", Source::Remote)?;
+        }
+        for tool in &self.tools {
+            let options = &tool.value.options;
+            writeln!(f)?;
+            writeln!(f, "[tools.{}]  # {}", tool.value.name.as_str(), tool.source)?;
+            writeln!(f, "rules = {:?}", options.rules)?;
+            writeln!(f, "exclude = {:?}", options.exclude)?;
+            if let Some(timeout) = options.timeout {
+                writeln!(f, "timeout = {timeout}")?;
+            }
+            if let Some(max_memory_mb) = options.max_memory_mb {
+                writeln!(f, "max_memory_mb = {max_memory_mb}")?;
+            }
+            if let Some(max_cpu_seconds) = options.max_cpu_seconds {
+                writeln!(f, "max_cpu_seconds = {max_cpu_seconds}")?;
+            }
+            writeln!(f, "extra_args = {:?}", options.extra_args)?;
+            if let Some(rules_version) = &options.rules_version {
+                writeln!(f, "rules_version = {rules_version:?}")?;
+            }
+            if let Some(rules_sha256) = &options.rules_sha256 {
+                writeln!(f, "rules_sha256 = {rules_sha256:?}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remote(tools: Vec<ToolConfig>) -> AmplifyConfigResponse {
+        AmplifyConfigResponse {
+            tools,
+            merge_comments_enabled: true,
+            merge_approvals_enabled: false,
+            deleted: false,
+            project_url: None,
+        }
+    }
+
+    fn repo(toml: &str) -> Option<RepoConfig> {
+        Some(toml::from_str(toml).unwrap())
+    }
+
+    #[test]
+    fn test_defaults_without_overrides() {
+        let config = EffectiveConfig::merge(
+            Overrides::default(),
+            Overrides::default(),
+            None,
+            remote(vec![Tools::Semgrep.into()]),
+        )
+        .unwrap();
+
+        assert_eq!(config.exclude, Setting::new(vec![], Source::Default));
+        assert_eq!(config.paths.value, vec!["."]);
+        assert_eq!(config.tools.len(), 1);
+        assert_eq!(config.tools[0].source, Source::Remote);
+        assert!(config.settings.merge_comments_enabled);
+    }
+
+    #[test]
+    fn test_precedence_cli_over_env_over_repo_file() {
+        let repo = repo(r#"exclude = ["from-repo/"]"#);
+        let env = Overrides {
+            exclude: Some(vec!["from-env/".into()]),
+            rules: Some(vec!["env.yml".into()]),
+            paths: Some(vec!["src".into()]),
+            monorepo: Some(false),
+            jobs: Some(4),
+            failure_policy: Some(FailurePolicy::Continue),
+            run_timeout: Some(1800),
+            max_artifact_mb: None,
+            fail_on: Some(Severity::Medium),
+            max_annotations: Some(0),
+        };
+        let cli = Overrides {
+            exclude: Some(vec!["from-cli/".into()]),
+            rules: None,
+            paths: None,
+            monorepo: Some(true),
+            jobs: None,
+            failure_policy: None,
+            run_timeout: None,
+            max_artifact_mb: Some(250),
+            fail_on: None,
+            max_annotations: None,
+        };
+
+        let config =
+            EffectiveConfig::merge(cli, env.clone(), repo.clone(), remote(vec![])).unwrap();
+        assert_eq!(config.exclude.value, vec!["from-cli/"]);
+        assert_eq!(config.exclude.source, Source::Cli);
+        assert_eq!(config.rules.value, vec!["env.yml"]);
+        assert_eq!(config.rules.source, Source::Env);
+        assert_eq!(config.paths.value, vec!["src"]);
+        assert_eq!(config.paths.source, Source::Env);
+        assert_eq!(config.monorepo, Setting::new(true, Source::Cli));
+        assert_eq!(config.jobs, Setting::new(4, Source::Env));
+        assert_eq!(
+            config.failure_policy,
+            Setting::new(FailurePolicy::Continue, Source::Env)
+        );
+        assert_eq!(config.run_timeout, Setting::new(Some(1800), Source::Env));
+        assert_eq!(config.max_artifact_mb, Setting::new(250, Source::Cli));
+        assert_eq!(
+            config.fail_on,
+            Setting::new(Some(Severity::Medium), Source::Env)
+        );
+        assert_eq!(config.max_annotations, Setting::new(0, Source::Env));
+
+        let config = EffectiveConfig::merge(
+            Overrides::default(),
+            Overrides::default(),
+            repo,
+            remote(vec![]),
+        )
+        .unwrap();
+        assert_eq!(config.exclude.value, vec!["from-repo/"]);
+        assert_eq!(config.exclude.source, Source::RepoFile);
+    }
+
+    #[test]
+    fn test_repo_file_overrides_remote_tool_options() {
+        let mut semgrep = ToolConfig::from(Tools::Semgrep);
+        semgrep.options.timeout = Some(60);
+        semgrep.options.rules = vec!["remote.yml".into()];
+        let repo = repo(
+            r#"
+            [tools.semgrep]
+            timeout = 900
+            "#,
+        );
+
+        let config = EffectiveConfig::merge(
+            Overrides::default(),
+            Overrides::default(),
+            repo,
+            remote(vec![semgrep]),
+        )
+        .unwrap();
+
+        let tool = &config.tools[0];
+        assert_eq!(tool.source, Source::RepoFile);
+        assert_eq!(tool.value.options.timeout, Some(900));
+        assert_eq!(tool.value.options.rules, vec!["remote.yml"]);
+    }
+
+    #[test]
+    fn test_repo_file_enables_and_disables_tools() {
+        let repo = repo(
+            r#"
+            [tools.semgrep]
+            enabled = false
+
+            [tools.UNAME]
+            enabled = true
+            "#,
+        );
+
+        let config = EffectiveConfig::merge(
+            Overrides::default(),
+            Overrides::default(),
+            repo,
+            remote(vec![Tools::Semgrep.into()]),
+        )
+        .unwrap();
+
+        assert_eq!(config.tools.len(), 1);
+        assert_eq!(config.tools[0].value.name, Tools::Uname);
+        assert_eq!(config.tools[0].source, Source::RepoFile);
+    }
+
+    #[test]
+    fn test_unknown_tool_in_repo_file_is_an_error() {
+        let repo = repo("[tools.nope]\nenabled = true");
+        let result = EffectiveConfig::merge(
+            Overrides::default(),
+            Overrides::default(),
+            repo,
+            remote(vec![]),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_tool_configs_include_global_excludes_and_rules() {
+        let mut semgrep = ToolConfig::from(Tools::Semgrep);
+        semgrep.options.exclude = vec!["tool/".into()];
+        let cli = Overrides {
+            exclude: Some(vec!["global/".into()]),
+            rules: Some(vec!["extra.yml".into()]),
+            ..Overrides::default()
+        };
+
+        let config =
+            EffectiveConfig::merge(cli, Overrides::default(), None, remote(vec![semgrep])).unwrap();
+        let tools = config.tool_configs();
+
+        assert_eq!(tools[0].options.exclude, vec!["tool/", "global/"]);
+        assert_eq!(tools[0].options.rules, vec!["extra.yml"]);
+    }
+
+    #[test]
+    fn test_project_roots() {
+        let config = EffectiveConfig::merge(
+            Overrides::default(),
+            Overrides::default(),
+            repo(r#"projects = ["services/api"]"#),
+            remote(vec![]),
+        )
+        .unwrap();
+        assert_eq!(config.project_roots(), None);
+
+        let config = EffectiveConfig::merge(
+            Overrides::default(),
+            Overrides::default(),
+            repo("monorepo = true\nprojects = [\"src\"]"),
+            remote(vec![]),
+        )
+        .unwrap();
+        assert_eq!(config.project_roots(), Some(vec!["src".into()]));
+    }
+
+    #[test]
+    fn test_missing_project_is_an_error() {
+        let result = EffectiveConfig::merge(
+            Overrides::default(),
+            Overrides::default(),
+            repo("monorepo = true\nprojects = [\"services/api\"]"),
+            remote(vec![]),
+        );
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Project `services/api` from the .amplify/runner.toml"));
+    }
+
+    #[test]
+    fn test_missing_scan_path_is_an_error() {
+        let cli = Overrides {
+            paths: Some(vec!["does/not/exist".into()]),
+            ..Overrides::default()
+        };
+        let result = EffectiveConfig::merge(cli, Overrides::default(), None, remote(vec![]));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_zero_jobs_is_an_error() {
+        let cli = Overrides {
+            jobs: Some(0),
+            ..Overrides::default()
+        };
+        let result = EffectiveConfig::merge(cli, Overrides::default(), None, remote(vec![]));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_run_timeout() {
+        let config = EffectiveConfig::merge(
+            Overrides::default(),
+            Overrides::default(),
+            None,
+            remote(vec![]),
+        )
+        .unwrap();
+        assert_eq!(config.run_timeout, Setting::new(None, Source::Default));
+
+        let config = EffectiveConfig::merge(
+            Overrides::default(),
+            Overrides::default(),
+            repo("run_timeout = 3600"),
+            remote(vec![]),
+        )
+        .unwrap();
+        assert_eq!(
+            config.run_timeout,
+            Setting::new(Some(3600), Source::RepoFile)
+        );
+
+        let cli = Overrides {
+            run_timeout: Some(0),
+            ..Overrides::default()
+        };
+        let result = EffectiveConfig::merge(cli, Overrides::default(), None, remote(vec![]));
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_env_lists_are_comma_separated() {
+        let _lock = crate::common::test_support::ENV_MUTEX.lock().await;
+        std::env::set_var(ENV_EXCLUDE, "vendor/, dist/ ,,");
+        std::env::remove_var(ENV_RULES);
+
+        let env = Overrides::from_env().unwrap();
+
+        assert_eq!(env.exclude, Some(vec!["vendor/".into(), "dist/".into()]));
+        assert_eq!(env.rules, None);
+        std::env::remove_var(ENV_EXCLUDE);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_env_values_are_errors() {
+        let _lock = crate::common::test_support::ENV_MUTEX.lock().await;
+        std::env::set_var(ENV_FAIL_ON, "hihg");
+        let error = Overrides::from_env().unwrap_err().to_string();
+        assert!(error.contains(ENV_FAIL_ON), "{error}");
+        assert!(error.contains("Unknown severity `hihg`"), "{error}");
+
+        std::env::set_var(ENV_FAIL_ON, " high ");
+        std::env::set_var(ENV_JOBS, "");
+        let env = Overrides::from_env().unwrap();
+        assert_eq!(env.fail_on, Some(Severity::High));
+        assert_eq!(env.jobs, None);
+
+        std::env::set_var(ENV_JOBS, "four");
+        assert!(Overrides::from_env().is_err());
+        std::env::set_var(ENV_JOBS, "4");
+        std::env::set_var(ENV_MONOREPO, "yes");
+        assert!(Overrides::from_env().is_err());
+        std::env::remove_var(ENV_FAIL_ON);
+        std::env::remove_var(ENV_JOBS);
+        std::env::remove_var(ENV_MONOREPO);
+    }
+
+    #[test]
+    fn test_failure_policy_from_repo_file() {
+        let config = EffectiveConfig::merge(
+            Overrides::default(),
+            Overrides::default(),
+            repo(r#"failure_policy = "best-effort""#),
+            remote(vec![]),
+        )
+        .unwrap();
+        assert_eq!(
+            config.failure_policy,
+            Setting::new(FailurePolicy::BestEffort, Source::RepoFile)
+        );
+        assert!(toml::from_str::<RepoConfig>(r#"failure_policy = "never""#).is_err());
+    }
+
+    #[test]
+    fn test_fail_on_from_repo_file() {
+        let config = EffectiveConfig::merge(
+            Overrides::default(),
+            Overrides::default(),
+            repo(r#"fail_on = "high""#),
+            remote(vec![]),
+        )
+        .unwrap();
+        assert_eq!(
+            config.fail_on,
+            Setting::new(Some(Severity::High), Source::RepoFile)
+        );
+        assert!(toml::from_str::<RepoConfig>(r#"fail_on = "severe""#).is_err());
+        assert_eq!(RepoConfig::default().fail_on, None);
+    }
+
+    #[test]
+    fn test_repo_file_rejects_unknown_keys() {
+        assert!(toml::from_str::<RepoConfig>("excludes = []").is_err());
+    }
+}
@@ -0,0 +1,186 @@
+//! Diff-aware scanning for pull and merge requests.
+//!
+//! On a pull or merge request pipeline, tools that support it only report
+//! findings introduced since the commit the change is based on. The base is
+//! taken from `--baseline-commit` or `AMPLIFY_BASELINE_COMMIT`, or else
+//! detected from the CI environment. When the base isn't in the local
+//! history, usually because the checkout is shallow, the whole repository is
+//! scanned instead.
+//!
+//! Like `.amplify/runner.toml` and `.amplify/rules/`, the history comes from
+//! the working directory's repository, even when the scan paths are in a
+//! repository of their own.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const ENV_BASELINE_COMMIT: &str = "AMPLIFY_BASELINE_COMMIT";
+
+/// The commit or ref that a pull or merge request is based on, according to
+/// the CI environment.
+fn base_ref_from_env() -> Option<String> {
+    let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    // https://docs.gitlab.com/ee/ci/variables/predefined_variables.html
+    if let Some(sha) = var("CI_MERGE_REQUEST_DIFF_BASE_SHA") {
+        return Some(sha);
+    }
+    // https://docs.github.com/en/actions/learn-github-actions/variables
+    let base_ref = var("GITHUB_BASE_REF")?;
+    var("GITHUB_EVENT_PATH")
+        .and_then(|path| github_event_base_sha(Path::new(&path)))
+        .or_else(|| Some(format!("origin/{base_ref}")))
+}
+
+/// The base commit of the pull request in a GitHub event payload.
+fn github_event_base_sha(path: &Path) -> Option<String> {
+    let event: serde_json::Value = serde_json::from_slice(&std::fs::read(path).ok()?).ok()?;
+    event
+        .pointer("/pull_request/base/sha")
+        .and_then(serde_json::Value::as_str)
+        .map(str::to_owned)
+}
+
+/// The commit where `HEAD` branched off from `base`, if both are in the
+/// local history.
+fn merge_base(base: &str) -> Option<String> {
+    let output = Command::new("git")
+        .args(["merge-base", base, "HEAD"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let sha = String::from_utf8(output.stdout).ok()?.trim().to_owned();
+    (!sha.is_empty()).then_some(sha)
+}
+
+/// The top-level directory of the Git repository that `dir` is in.
+fn git_toplevel(dir: &Path) -> Option<PathBuf> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--show-toplevel"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let toplevel = String::from_utf8(output.stdout).ok()?.trim().to_owned();
+    (!toplevel.is_empty()).then(|| PathBuf::from(toplevel))
+}
+
+/// Scan paths that are in a different Git repository from the working
+/// directory, such as one checked out into a subdirectory, along with the
+/// top-level directory of that repository.
+pub fn separate_checkouts(paths: &[String]) -> Vec<(String, PathBuf)> {
+    let current = git_toplevel(Path::new("."));
+    paths
+        .iter()
+        .filter_map(|path| {
+            let path_buf = Path::new(path);
+            let dir = match path_buf.parent() {
+                _ if path_buf.is_dir() => path_buf,
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            let toplevel = git_toplevel(dir)?;
+            (current.as_ref() != Some(&toplevel)).then(|| (path.clone(), toplevel))
+        })
+        .collect()
+}
+
+/// Decide which commit to scan against, or `None` for a full scan.
+pub fn baseline_commit(cli: Option<String>, full_scan: bool) -> Option<String> {
+    if full_scan {
+        return None;
+    }
+    let base = cli
+        .or_else(|| {
+            std::env::var(ENV_BASELINE_COMMIT)
+                .ok()
+                .filter(|value| !value.is_empty())
+        })
+        .or_else(base_ref_from_env)?;
+    match merge_base(&base) {
+        Some(commit) => {
+            println!("Scanning for findings introduced since {commit} (base {base}).");
+            Some(commit)
+        }
+        None => {
+            println!(
+                "::notice::Base commit {base} is not in the local history, so the whole repository will be scanned. Fetch more history, e.g. with `fetch-depth: 0`, to only report new findings."
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_support::TempDir;
+
+    #[test]
+    fn test_separate_checkouts() {
+        let checkout = TempDir::new("checkout");
+        let status = Command::new("git")
+            .arg("init")
+            .arg("--quiet")
+            .arg(&*checkout)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let path = checkout.to_string_lossy().into_owned();
+
+        let separate = separate_checkouts(&[".".to_owned(), path.clone()]);
+
+        assert_eq!(separate.len(), 1);
+        assert_eq!(separate[0].0, path);
+        assert_eq!(
+            separate[0].1.canonicalize().unwrap(),
+            checkout.canonicalize().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_github_event_base_sha() {
+        let dir = TempDir::new("event");
+        let path = dir.join("event.json");
+        std::fs::write(
+            &path,
+            r#"{"pull_request":{"base":{"ref":"main","sha":"abc123"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(github_event_base_sha(&path), Some("abc123".to_owned()));
+
+        std::fs::write(&path, r#"{"ref":"refs/heads/main"}"#).unwrap();
+        assert_eq!(github_event_base_sha(&path), None);
+    }
+
+    #[tokio::test]
+    async fn test_base_ref_from_env() {
+        let _lock = crate::common::test_support::ENV_MUTEX.lock().await;
+        for name in [
+            "CI_MERGE_REQUEST_DIFF_BASE_SHA",
+            "GITHUB_BASE_REF",
+            "GITHUB_EVENT_PATH",
+        ] {
+            std::env::remove_var(name);
+        }
+        assert_eq!(base_ref_from_env(), None);
+
+        std::env::set_var("GITHUB_BASE_REF", "main");
+        assert_eq!(base_ref_from_env(), Some("origin/main".to_owned()));
+
+        std::env::set_var("CI_MERGE_REQUEST_DIFF_BASE_SHA", "def456");
+        assert_eq!(base_ref_from_env(), Some("def456".to_owned()));
+
+        std::env::remove_var("GITHUB_BASE_REF");
+        std::env::remove_var("CI_MERGE_REQUEST_DIFF_BASE_SHA");
+    }
+
+    #[test]
+    fn test_full_scan_ignores_the_baseline() {
+        assert_eq!(baseline_commit(Some("HEAD".to_owned()), true), None);
+    }
+}
@@ -0,0 +1,307 @@
+//! Output for GitHub Actions.
+//!
+//! Findings are reported as workflow annotations, which GitHub shows inline
+//! in a pull request's "Files changed" view, when merge comments are
+//! enabled for the project in Amplify. GitHub only shows a limited number
+//! of annotations per job, so the most severe findings go first and the
+//! rest are left out.
+//!
+//! The run as a whole is described in the job's step summary, a Markdown
+//! page shown on the workflow run: which tools ran and for how long, what
+//! they found, and a link to the project in Amplify.
+
+use color_eyre::eyre::{Result, WrapErr};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::time::Duration;
+
+use crate::scheduler::RunSummary;
+use crate::tools::opengrep::report::{Finding, Severity};
+
+/// Annotations to show by default. GitHub shows at most 50 per job.
+pub const DEFAULT_MAX_ANNOTATIONS: usize = 50;
+
+/// Rules to list in the step summary, most findings first.
+const MAX_TOP_RULES: usize = 10;
+
+/// Escape the message of a workflow command.
+pub(crate) fn escape_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape a property of a workflow command, such as `file` or `title`.
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+fn command(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical | Severity::High => "error",
+        Severity::Medium => "warning",
+        Severity::Low | Severity::Unknown => "notice",
+    }
+}
+
+fn findings(summary: &RunSummary) -> impl Iterator<Item = &Finding> {
+    summary
+        .outcomes
+        .iter()
+        .filter_map(|outcome| outcome.findings.as_ref())
+        .flat_map(|report| &report.results)
+}
+
+fn annotation(finding: &Finding) -> String {
+    let severity = finding.extra.severity;
+    let mut properties = vec![format!("file={}", escape_property(&finding.path))];
+    if finding.start.line > 0 {
+        properties.push(format!("line={}", finding.start.line));
+        properties.push(format!(
+            "endLine={}",
+            finding.end.line.max(finding.start.line)
+        ));
+    }
+    properties.push(format!(
+        "title={}",
+        escape_property(&format!("{} ({severity})", finding.check_id))
+    ));
+    format!(
+        "::{} {}::{}",
+        command(severity),
+        properties.join(","),
+        escape_data(finding.extra.message.trim())
+    )
+}
+
+/// Workflow commands that annotate the code with the findings in `summary`,
+/// at most `max` of them, most severe first.
+pub fn annotations(summary: &RunSummary, max: usize) -> Vec<String> {
+    if max == 0 {
+        return Vec::new();
+    }
+    let mut findings: Vec<&Finding> = findings(summary).collect();
+    findings.sort_by(|a, b| {
+        b.extra
+            .severity
+            .cmp(&a.extra.severity)
+            .then_with(|| a.path.cmp(&b.path))
+            .then_with(|| a.start.line.cmp(&b.start.line))
+    });
+    let mut commands: Vec<String> = findings.iter().take(max).map(|f| annotation(f)).collect();
+    if findings.len() > max {
+        commands.push(format!(
+            "::notice::Showing {max} of {} findings as annotations. Set `max_annotations` to show more.",
+            findings.len()
+        ));
+    }
+    commands
+}
+
+/// Make `value` safe to put in a cell of a Markdown table.
+fn table_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+fn format_duration(duration: Option<Duration>) -> String {
+    match duration {
+        Some(duration) => format!("{:.1}s", duration.as_secs_f64()),
+        None => "-".to_owned(),
+    }
+}
+
+/// Markdown for the job's step summary, describing every scan in `summary`
+/// and linking to the project in Amplify when its URL is known.
+pub fn step_summary(summary: &RunSummary, project_url: Option<&str>) -> String {
+    let mut lines = vec![
+        "## Amplify scan".to_owned(),
+        String::new(),
+        format!("Scanned {} line(s) of code.", summary.code_lines),
+        String::new(),
+        "| Tool | Version | Duration | Status |".to_owned(),
+        "| --- | --- | --- | --- |".to_owned(),
+    ];
+    for outcome in &summary.outcomes {
+        let tool = match &outcome.project_path {
+            Some(project_path) => format!("{} ({project_path})", outcome.tool),
+            None => outcome.tool.to_owned(),
+        };
+        let version = outcome
+            .findings
+            .as_ref()
+            .and_then(|report| report.version.as_deref())
+            .unwrap_or("-");
+        lines.push(format!(
+            "| {} | {} | {} | {} |",
+            table_cell(&tool),
+            table_cell(version),
+            format_duration(outcome.duration),
+            table_cell(&outcome.status.to_string())
+        ));
+    }
+    lines.push(String::new());
+
+    let mut counts: BTreeMap<Severity, usize> = BTreeMap::new();
+    let mut rules: BTreeMap<&str, (Severity, usize)> = BTreeMap::new();
+    for finding in findings(summary) {
+        *counts.entry(finding.extra.severity).or_default() += 1;
+        let rule = rules
+            .entry(finding.check_id.as_str())
+            .or_insert((finding.extra.severity, 0));
+        rule.0 = rule.0.max(finding.extra.severity);
+        rule.1 += 1;
+    }
+    lines.push("### Findings".to_owned());
+    lines.push(String::new());
+    if counts.is_empty() {
+        lines.push("No findings.".to_owned());
+    } else {
+        lines.push("| Severity | Count |".to_owned());
+        lines.push("| --- | --- |".to_owned());
+        for severity in Severity::ALL {
+            if let Some(count) = counts.get(&severity) {
+                lines.push(format!("| {severity} | {count} |"));
+            }
+        }
+        lines.push(String::new());
+
+        let mut rules: Vec<(&str, (Severity, usize))> = rules.into_iter().collect();
+        rules.sort_by(|(_, (a_severity, a_count)), (_, (b_severity, b_count))| {
+            b_count.cmp(a_count).then(b_severity.cmp(a_severity))
+        });
+        lines.push("### Top rules".to_owned());
+        lines.push(String::new());
+        lines.push("| Rule | Severity | Findings |".to_owned());
+        lines.push("| --- | --- | --- |".to_owned());
+        for (rule, (severity, count)) in rules.iter().take(MAX_TOP_RULES) {
+            lines.push(format!("| `{}` | {severity} | {count} |", table_cell(rule)));
+        }
+    }
+
+    if let Some(project_url) = project_url {
+        lines.push(String::new());
+        lines.push(format!("[View the project in Amplify]({project_url})"));
+    }
+    let mut markdown = lines.join("\n");
+    markdown.push('\n');
+    markdown
+}
+
+/// Append `markdown` to the job's step summary. Does nothing outside of a
+/// GitHub Actions job.
+pub fn write_step_summary(markdown: &str) -> Result<()> {
+    let Ok(path) = std::env::var("GITHUB_STEP_SUMMARY") else {
+        return Ok(());
+    };
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(markdown.as_bytes()))
+        .wrap_err_with(|| format!("Failed to write the step summary to {path}."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_support::summary;
+    use crate::scheduler::Status;
+
+    #[test]
+    fn test_annotations_are_sorted_by_severity() {
+        let run = summary(
+            &[Status::Succeeded],
+            Some(
+                r#"{"results":[
+                {"check_id":"weak-hash","path":"b.py","start":{"line":1},"end":{"line":1},"extra":{"message":"md5","severity":"INFO"}},
+                {"check_id":"no-eval","path":"a,1.py","start":{"line":3},"end":{"line":4},"extra":{"message":"100% bad\nreally","severity":"ERROR"}}
+            ]}"#,
+            ),
+        );
+
+        assert_eq!(
+            annotations(&run, 10),
+            [
+                "::error file=a%2C1.py,line=3,endLine=4,title=no-eval (high)::100%25 bad%0Areally",
+                "::notice file=b.py,line=1,endLine=1,title=weak-hash (low)::md5",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_annotations_are_capped() {
+        let run = summary(
+            &[Status::Succeeded],
+            Some(
+                r#"{"results":[
+                {"check_id":"a","path":"a.py","extra":{"severity":"WARNING"}},
+                {"check_id":"b","path":"b.py","extra":{"severity":"WARNING"}}
+            ]}"#,
+            ),
+        );
+
+        let commands = annotations(&run, 1);
+
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0], "::warning file=a.py,title=a (medium)::");
+        assert!(commands[1].starts_with("::notice::Showing 1 of 2 findings"));
+        assert!(annotations(&run, 0).is_empty());
+    }
+
+    #[test]
+    fn test_step_summary() {
+        let mut run = summary(
+            &[Status::Succeeded],
+            Some(
+                r#"{"version":"1.16.1","results":[
+                {"check_id":"no-eval","path":"a.py","extra":{"severity":"ERROR"}},
+                {"check_id":"weak-hash","path":"a.py","extra":{"severity":"INFO"}},
+                {"check_id":"weak-hash","path":"b.py","extra":{"severity":"INFO"}}
+            ]}"#,
+            ),
+        );
+        run.outcomes[0].duration = Some(Duration::from_millis(12_340));
+        run.code_lines = 1200;
+
+        assert_eq!(
+            step_summary(&run, Some("https://amplify.example/projects/7")),
+            "## Amplify scan\n\
+             \n\
+             Scanned 1200 line(s) of code.\n\
+             \n\
+             | Tool | Version | Duration | Status |\n\
+             | --- | --- | --- | --- |\n\
+             | opengrep | 1.16.1 | 12.3s | succeeded |\n\
+             \n\
+             ### Findings\n\
+             \n\
+             | Severity | Count |\n\
+             | --- | --- |\n\
+             | high | 1 |\n\
+             | low | 2 |\n\
+             \n\
+             ### Top rules\n\
+             \n\
+             | Rule | Severity | Findings |\n\
+             | --- | --- | --- |\n\
+             | `weak-hash` | low | 2 |\n\
+             | `no-eval` | high | 1 |\n\
+             \n\
+             [View the project in Amplify](https://amplify.example/projects/7)\n"
+        );
+    }
+
+    #[test]
+    fn test_step_summary_without_findings() {
+        let run = summary(
+            &[Status::Failed("exit code 2 | oops".into())],
+            Some(r#"{"results":[]}"#),
+        );
+
+        let markdown = step_summary(&run, None);
+
+        assert!(markdown.contains("| opengrep | - | - | failed: exit code 2 \\| oops |"));
+        assert!(markdown.ends_with("### Findings\n\nNo findings.\n"));
+    }
+}
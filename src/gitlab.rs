@@ -0,0 +1,378 @@
+//! Output for GitLab CI.
+//!
+//! GitLab shows security findings in a merge request's widget and its
+//! vulnerability report when a job uploads them as a SAST report artifact.
+//! The report follows GitLab's security report schema, and every finding
+//! carries the identifiers its rule declares, such as CWE and OWASP
+//! categories, so GitLab can group and track it across pipelines.
+
+use color_eyre::eyre::{Result, WrapErr};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::scheduler::RunSummary;
+use crate::tools::opengrep::report::{Finding, Severity};
+
+/// Where GitLab jobs write the SAST report unless told otherwise. The job
+/// still has to list it under `artifacts:reports:sast`.
+pub const SAST_REPORT_PATH: &str = "gl-sast-report.json";
+
+/// Version of GitLab's security report schema the report follows.
+const SCHEMA_VERSION: &str = "15.0.7";
+
+#[derive(Debug, Serialize)]
+pub struct SastReport {
+    version: &'static str,
+    scan: Scan,
+    vulnerabilities: Vec<Vulnerability>,
+}
+
+#[derive(Debug, Serialize)]
+struct Scan {
+    analyzer: Component,
+    scanner: Component,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    start_time: String,
+    end_time: String,
+    status: &'static str,
+}
+
+/// The analyzer or scanner that produced the report.
+#[derive(Debug, Serialize)]
+struct Component {
+    id: &'static str,
+    name: &'static str,
+    version: String,
+    vendor: Vendor,
+}
+
+#[derive(Debug, Serialize)]
+struct Vendor {
+    name: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct Vulnerability {
+    id: String,
+    name: String,
+    description: String,
+    severity: &'static str,
+    identifiers: Vec<Identifier>,
+    location: Location,
+}
+
+#[derive(Debug, Serialize)]
+struct Identifier {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    name: String,
+    value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Location {
+    file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_line: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end_line: Option<u64>,
+}
+
+fn severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical => "Critical",
+        Severity::High => "High",
+        Severity::Medium => "Medium",
+        Severity::Low => "Low",
+        Severity::Unknown => "Unknown",
+    }
+}
+
+/// Rule metadata that may be a single string or a list of them.
+fn metadata_strings<'a>(finding: &'a Finding, key: &str) -> Vec<&'a str> {
+    match &finding.extra.metadata[key] {
+        serde_json::Value::String(value) => vec![value],
+        serde_json::Value::Array(values) => values
+            .iter()
+            .filter_map(serde_json::Value::as_str)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The rule itself, followed by the CWE and OWASP categories in its
+/// metadata, e.g. `CWE-95: Improper Neutralization...` and
+/// `A03:2021 - Injection`.
+fn identifiers(finding: &Finding) -> Vec<Identifier> {
+    // GitLab's own Semgrep analyzer uses this type for rule IDs, which lets
+    // it match findings from either.
+    let mut identifiers = vec![Identifier {
+        kind: "semgrep_id",
+        name: finding.check_id.clone(),
+        value: finding.check_id.clone(),
+        url: None,
+    }];
+    for cwe in metadata_strings(finding, "cwe") {
+        let number: String = cwe
+            .trim_start_matches("CWE-")
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .collect();
+        if number.is_empty() {
+            continue;
+        }
+        identifiers.push(Identifier {
+            kind: "cwe",
+            name: cwe.trim().to_owned(),
+            url: Some(format!(
+                "https://cwe.mitre.org/data/definitions/{number}.html"
+            )),
+            value: number,
+        });
+    }
+    for owasp in metadata_strings(finding, "owasp") {
+        let value = owasp.split(" - ").next().unwrap_or(owasp).trim();
+        identifiers.push(Identifier {
+            kind: "owasp",
+            name: owasp.trim().to_owned(),
+            value: value.to_owned(),
+            url: None,
+        });
+    }
+    identifiers
+}
+
+/// An ID that stays the same across runs for the same finding, so GitLab
+/// doesn't report it as new every time. The rule ID no longer has the run's
+/// work directory in it by now, and the rule source keeps a custom rule
+/// apart from an Amplify rule with the same ID.
+fn vulnerability_id(finding: &Finding) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(finding.check_id.as_bytes());
+    hasher.update([0]);
+    if let Some(source) = finding.extra.metadata["rule_source"].as_str() {
+        hasher.update(source.as_bytes());
+    }
+    hasher.update([0]);
+    hasher.update(finding.path.as_bytes());
+    hasher.update([0]);
+    hasher.update(finding.start.line.to_be_bytes());
+    hasher.update(finding.start.col.to_be_bytes());
+    const_hex::encode(hasher.finalize())
+}
+
+fn vulnerability(finding: &Finding) -> Vulnerability {
+    let message = finding.extra.message.trim();
+    let line = |line: u64| (line > 0).then_some(line);
+    Vulnerability {
+        id: vulnerability_id(finding),
+        name: message
+            .lines()
+            .next()
+            .filter(|name| !name.is_empty())
+            .unwrap_or(&finding.check_id)
+            .to_owned(),
+        description: message.to_owned(),
+        severity: severity(finding.extra.severity),
+        identifiers: identifiers(finding),
+        location: Location {
+            file: finding.path.clone(),
+            start_line: line(finding.start.line),
+            end_line: line(finding.end.line.max(finding.start.line)),
+        },
+    }
+}
+
+/// Format `time` as GitLab expects, e.g. `2024-05-01T12:30:00`, in UTC.
+fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default();
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+    // Days since the epoch to a civil date, from Howard Hinnant's
+    // `civil_from_days`.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+        secs / 3_600,
+        secs % 3_600 / 60,
+        secs % 60
+    )
+}
+
+/// SAST report of the findings in `summary`, for a run between `started`
+/// and `finished`.
+pub fn sast_report(summary: &RunSummary, started: SystemTime, finished: SystemTime) -> SastReport {
+    let reports: Vec<_> = summary
+        .outcomes
+        .iter()
+        .filter_map(|outcome| outcome.findings.as_ref())
+        .collect();
+    let scanner_version = reports
+        .iter()
+        .find_map(|report| report.version.clone())
+        .unwrap_or_else(|| "unknown".to_owned());
+    let failed = summary
+        .outcomes
+        .iter()
+        .any(|outcome| outcome.status.is_failure());
+    SastReport {
+        version: SCHEMA_VERSION,
+        scan: Scan {
+            analyzer: Component {
+                id: "amplify-runner",
+                name: "Amplify Runner",
+                version: env!("CARGO_PKG_VERSION").to_owned(),
+                vendor: Vendor {
+                    name: "Amplify Security",
+                },
+            },
+            scanner: Component {
+                id: "opengrep",
+                name: "Opengrep",
+                version: scanner_version,
+                vendor: Vendor { name: "Opengrep" },
+            },
+            kind: "sast",
+            start_time: timestamp(started),
+            end_time: timestamp(finished),
+            status: if failed { "failure" } else { "success" },
+        },
+        vulnerabilities: reports
+            .iter()
+            .flat_map(|report| &report.results)
+            .map(vulnerability)
+            .collect(),
+    }
+}
+
+/// Write `report` to `path` as JSON.
+pub fn write_sast_report(report: &SastReport, path: &Path) -> Result<()> {
+    let json =
+        serde_json::to_vec_pretty(report).wrap_err("Failed to serialize the SAST report.")?;
+    std::fs::write(path, json)
+        .wrap_err_with(|| format!("Failed to write the SAST report to {}.", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_support::summary;
+    use crate::scheduler::Status;
+    use crate::tools::opengrep::report::Report;
+    use crate::tools::opengrep::rules::{self, RuleSources};
+    use std::time::Duration;
+
+    const REPORT: &str = r#"{"version":"1.16.1","results":[
+        {"check_id":"python.lang.security.no-eval","path":"src/a.py","start":{"line":3,"col":5},"end":{"line":4,"col":1},
+         "extra":{"message":"Avoid eval.\nIt runs arbitrary code.","severity":"ERROR","metadata":{
+            "cwe":["CWE-95: Improper Neutralization of Directives in Dynamically Evaluated Code ('Eval Injection')"],
+            "owasp":"A03:2021 - Injection"}}},
+        {"check_id":"inventory","path":"src/b.py","extra":{"severity":"INVENTORY"}}
+    ]}"#;
+
+    #[test]
+    fn test_sast_report() {
+        let started = UNIX_EPOCH + Duration::from_secs(1_714_566_600);
+        let finished = started + Duration::from_secs(65);
+        let report = sast_report(
+            &summary(&[Status::Succeeded], Some(REPORT)),
+            started,
+            finished,
+        );
+        let json = serde_json::to_value(&report).unwrap();
+
+        assert_eq!(json["version"], SCHEMA_VERSION);
+        assert_eq!(json["scan"]["type"], "sast");
+        assert_eq!(json["scan"]["scanner"]["version"], "1.16.1");
+        assert_eq!(json["scan"]["start_time"], "2024-05-01T12:30:00");
+        assert_eq!(json["scan"]["end_time"], "2024-05-01T12:31:05");
+        assert_eq!(json["scan"]["status"], "success");
+
+        let eval = &json["vulnerabilities"][0];
+        assert_eq!(eval["name"], "Avoid eval.");
+        assert_eq!(eval["severity"], "High");
+        assert_eq!(eval["id"].as_str().unwrap().len(), 64);
+        assert_eq!(
+            eval["location"],
+            serde_json::json!({"file": "src/a.py", "start_line": 3, "end_line": 4})
+        );
+        assert_eq!(
+            eval["identifiers"],
+            serde_json::json!([
+                {"type": "semgrep_id", "name": "python.lang.security.no-eval", "value": "python.lang.security.no-eval"},
+                {"type": "cwe", "name": "CWE-95: Improper Neutralization of Directives in Dynamically Evaluated Code ('Eval Injection')",
+                 "value": "95", "url": "https://cwe.mitre.org/data/definitions/95.html"},
+                {"type": "owasp", "name": "A03:2021 - Injection", "value": "A03:2021"}
+            ])
+        );
+
+        let inventory = &json["vulnerabilities"][1];
+        assert_eq!(inventory["name"], "inventory");
+        assert_eq!(inventory["severity"], "Unknown");
+        assert_eq!(
+            inventory["location"],
+            serde_json::json!({"file": "src/b.py"})
+        );
+    }
+
+    #[test]
+    fn test_failed_scan_is_reported() {
+        let report = sast_report(
+            &summary(&[Status::Failed("exit code 2".into())], Some(REPORT)),
+            UNIX_EPOCH,
+            UNIX_EPOCH,
+        );
+        assert_eq!(report.scan.status, "failure");
+        assert_eq!(report.scan.start_time, "1970-01-01T00:00:00");
+    }
+
+    #[test]
+    fn test_vulnerability_ids_are_the_same_in_every_work_dir() {
+        let ids: Vec<String> = ["/tmp/amplify-runner-a1b2c3", "/tmp/amplify-runner-d4e5f6"]
+            .into_iter()
+            .map(|work_dir| {
+                let mut report = Report::parse(&format!(
+                    r#"{{"results":[{{"check_id":"{}.no-eval","path":"a.py"}}]}}"#,
+                    work_dir.trim_start_matches('/').replace('/', ".")
+                ))
+                .unwrap();
+                let ruleset = Path::new(work_dir).join("ruleset.json");
+                rules::tag_rule_sources(&mut report, &RuleSources::new(&ruleset, &[]));
+                vulnerability_id(&report.results[0])
+            })
+            .collect();
+        assert_eq!(ids[0], ids[1]);
+
+        let mut custom = Report::parse(
+            r#"{"results":[{"check_id":"no-eval","path":"a.py","extra":{"metadata":{"rule_source":"custom"}}}]}"#,
+        )
+        .unwrap();
+        assert_ne!(vulnerability_id(&custom.results.remove(0)), ids[0]);
+    }
+
+    #[test]
+    fn test_timestamp() {
+        // A leap day, to check the date arithmetic.
+        let time = UNIX_EPOCH + Duration::from_secs(951_782_399);
+        assert_eq!(timestamp(time), "2000-02-28T23:59:59");
+        assert_eq!(
+            timestamp(time + Duration::from_secs(1)),
+            "2000-02-29T00:00:00"
+        );
+    }
+}
@@ -4,14 +4,35 @@
 //! interfaces with Amplify's API to provide remediations to the user.
 
 use color_eyre::eyre::{eyre, Result, WrapErr};
+use std::path::PathBuf;
 use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 pub(crate) mod amplify;
+pub(crate) mod amplifyignore;
 pub(crate) mod auth;
+pub(crate) mod cache;
 pub(crate) mod cli;
 pub(crate) mod common;
+pub(crate) mod config;
+pub(crate) mod diff;
+pub(crate) mod github;
+pub(crate) mod gitlab;
+pub(crate) mod metrics;
+pub(crate) mod monorepo;
+pub(crate) mod profile;
+pub(crate) mod scheduler;
+pub(crate) mod shutdown;
+pub(crate) mod tools;
+pub(crate) mod workdir;
 
-use crate::amplify::{Tool, ToolActions};
+use crate::scheduler::{ScanTarget, Scheduler};
+use crate::tools::{RunContext, Tool};
+
+/// Exit code when every scan succeeded, but found issues at or above the
+/// `--fail-on` severity. Tool failures exit with 1 instead.
+const EXIT_FINDINGS_OVER_THRESHOLD: u8 = 3;
 
 #[tokio::main]
 async fn main() -> Result<ExitCode> {
@@ -26,8 +47,32 @@ async fn main() -> Result<ExitCode> {
         })
         .install()?;
 
+    let shutdown = shutdown::Shutdown::listen()?;
+    tokio::select! {
+        exit_code = run(&shutdown) => exit_code,
+        // Nothing needs to be cancelled before the scans start, and the work
+        // directory is removed when the run is dropped.
+        signal = shutdown.before_take_over() => Ok(ExitCode::from(signal.exit_code())),
+    }
+}
+
+async fn run(shutdown: &shutdown::Shutdown) -> Result<ExitCode> {
     let args = cli::init();
     let endpoint = args.endpoint.clone().unwrap();
+    let tool_cache = cache::ToolCache::resolve(args.cache_dir.clone());
+
+    if args.command == Some(cli::RunnerCommand::CachePrune) {
+        let removed = tool_cache.prune(&tools::cache_entries(), cache::PRUNE_UNUSED_FOR)?;
+        for dir in &removed {
+            println!("Removed {}.", dir.display());
+        }
+        println!(
+            "Pruned {} cached tool version(s) from {}.",
+            removed.len(),
+            tool_cache.root().display()
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
 
     if let Some(ci) = args.ci.clone() {
         let provider_token = match ci {
@@ -62,31 +107,168 @@ async fn main() -> Result<ExitCode> {
             amplify_auth.get_token().await?
         };
 
-        let config = if ci == cli::ExecutionEnvironment::Local {
+        let remote_config = if ci == cli::ExecutionEnvironment::Local {
+            // Every tool is enabled locally, and the ones that don't apply to
+            // the repository are skipped below.
             amplify::AmplifyConfigResponse {
-                tools: vec![amplify::Tools::Uname],
+                tools: amplify::Tools::ALL.into_iter().map(Into::into).collect(),
                 merge_comments_enabled: false,
                 merge_approvals_enabled: false,
                 deleted: false,
+                project_url: None,
             }
         } else {
             amplify::get_config(endpoint.to_owned(), amplify_token.to_owned()).await?
         };
 
-        let code_lines = amplify::get_code_lines();
-
-        for tool_name in config.tools.into_iter() {
-            let tool = Tool::new_from(tool_name);
-            tool.setup().await?;
-            let (tool_output_type, tool_output) = tool.launch().await?;
-            amplify::submit_artifact(
-                endpoint.to_owned(),
-                amplify_token.to_owned(),
-                tool_output,
-                tool_output_type,
-                code_lines,
-            )
-            .await?;
+        // Nothing else about a deleted project matters, including whether
+        // the rest of its configuration is valid.
+        if remote_config.deleted && args.command != Some(cli::RunnerCommand::ConfigShow) {
+            println!(
+                "::notice::This project is marked as deleted in Amplify, so no scans will be run."
+            );
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let config = config::EffectiveConfig::merge(
+            config::Overrides::from_args(&args),
+            config::Overrides::from_env()?,
+            config::RepoConfig::load()?,
+            remote_config,
+        )?;
+
+        if args.command == Some(cli::RunnerCommand::ConfigShow) {
+            print!("{config}");
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        for (path, toplevel) in diff::separate_checkouts(&config.paths.value) {
+            println!(
+                "::warning::Scan path {path} is in a separate Git repository at {}. Its .amplify/runner.toml, .amplify/rules/ and history are not used, since they are always read from the working directory; run the runner from that repository to use them.",
+                toplevel.display(),
+            );
+        }
+
+        let ctx = RunContext {
+            settings: config.settings,
+            paths: config.paths.value.clone(),
+            project_path: None,
+            ignore: amplifyignore::AmplifyIgnore::load(&config.paths.value)?,
+            cache: tool_cache,
+            work_dir: Arc::new(workdir::WorkDir::create(args.work_dir.clone())?),
+            baseline_commit: diff::baseline_commit(args.baseline_commit.clone(), args.full_scan),
+            deadline: config
+                .run_timeout
+                .value
+                .map(|secs| tokio::time::Instant::now() + Duration::from_secs(secs)),
+            max_artifact_bytes: Some(config.max_artifact_mb.value.saturating_mul(1024 * 1024)),
+        };
+        println!(
+            "Project settings: merge comments {}, merge approvals {}.",
+            enabled_str(ctx.settings.merge_comments_enabled),
+            enabled_str(ctx.settings.merge_approvals_enabled)
+        );
+        println!("Using work directory {}.", ctx.work_dir.root().display());
+
+        let targets = match config.project_roots() {
+            Some(roots) => {
+                if roots.is_empty() {
+                    return Err(eyre!(
+                        "Monorepo mode is enabled, but no projects were found in {:?}.",
+                        ctx.paths
+                    ));
+                }
+                println!("Found {} project(s): {}.", roots.len(), roots.join(", "));
+                let mut targets = Vec::new();
+                for root in roots {
+                    let project_ctx = RunContext {
+                        paths: vec![root.clone()],
+                        project_path: Some(root.clone()),
+                        ..ctx.clone()
+                    };
+                    targets.push(
+                        prepare_target(&endpoint, &amplify_token, project_ctx, Some(&root)).await,
+                    );
+                }
+                targets
+            }
+            None => vec![prepare_target(&endpoint, &amplify_token, ctx.clone(), None).await],
+        };
+
+        let tools = config
+            .tool_configs()
+            .into_iter()
+            .map(Tool::new_from)
+            .collect();
+        println!(
+            "Running up to {} tool(s) at a time, with the {} failure policy.",
+            config.jobs.value, config.failure_policy.value
+        );
+        if let Some(run_timeout) = config.run_timeout.value {
+            println!("Stopping any tools that are still running after {run_timeout} seconds.");
+        }
+        let started = SystemTime::now();
+        let summary = Scheduler::new(
+            endpoint,
+            amplify_token,
+            config.jobs.value,
+            config.failure_policy.value,
+            shutdown.clone(),
+        )
+        .run(&ctx, tools, targets)
+        .await;
+        print!("{summary}");
+        if ci == cli::ExecutionEnvironment::Github {
+            // Annotations show up inline on pull requests, like merge
+            // comments would, so they follow the same project setting.
+            if ctx.settings.merge_comments_enabled {
+                for annotation in github::annotations(&summary, config.max_annotations.value) {
+                    println!("{annotation}");
+                }
+            }
+            let markdown = github::step_summary(&summary, config.project_url.as_deref());
+            if let Err(e) = github::write_step_summary(&markdown) {
+                println!("::warning::{e}");
+            }
+        }
+        let mut reports = args.reports.clone();
+        if ci == cli::ExecutionEnvironment::Gitlab
+            && !reports
+                .iter()
+                .any(|report| report.format == cli::ReportFormat::GitlabSast)
+        {
+            reports.push(cli::ReportOutput {
+                format: cli::ReportFormat::GitlabSast,
+                path: PathBuf::from(gitlab::SAST_REPORT_PATH),
+            });
+        }
+        let finished = SystemTime::now();
+        for report in &reports {
+            let written = match report.format {
+                cli::ReportFormat::GitlabSast => gitlab::write_sast_report(
+                    &gitlab::sast_report(&summary, started, finished),
+                    &report.path,
+                ),
+            };
+            match written {
+                Ok(()) => println!("Wrote the findings report to {}.", report.path.display()),
+                Err(e) => println!("::warning::{e}"),
+            }
+        }
+        if let Some(signal) = shutdown.received() {
+            return Ok(ExitCode::from(signal.exit_code()));
+        }
+        if !summary.succeeded(config.failure_policy.value) {
+            return Ok(ExitCode::FAILURE);
+        }
+        if let Some(threshold) = config.fail_on.value {
+            let over_threshold = summary.findings_at_least(threshold);
+            if over_threshold > 0 {
+                println!(
+                    "::error::Found {over_threshold} finding(s) with {threshold} severity or higher."
+                );
+                return Ok(ExitCode::from(EXIT_FINDINGS_OVER_THRESHOLD));
+            }
         }
     } else {
         println!("CI environment is unknown! You may need to specify one via --ci.");
@@ -95,3 +277,50 @@ async fn main() -> Result<ExitCode> {
 
     Ok(ExitCode::SUCCESS)
 }
+
+/// Profile the paths in `ctx` and submit their code metrics, as the whole
+/// repository or as one project of a monorepo.
+async fn prepare_target(
+    endpoint: &str,
+    amplify_token: &str,
+    ctx: RunContext,
+    project_path: Option<&str>,
+) -> ScanTarget {
+    if let Some(project_path) = project_path {
+        println!("Profiling project {project_path}.");
+    }
+    let languages = metrics::get_languages(&ctx.paths);
+    let code_metrics = metrics::CodeMetrics::from_languages(&languages);
+    let metadata = amplify::ArtifactMetadata {
+        code_lines: code_metrics.total.code,
+        scan_paths: ctx.paths.clone(),
+        project_path: project_path.map(str::to_owned),
+    };
+    let profile = profile::RepoProfile::new(&languages, &ctx.paths);
+    println!("Repository profile: {profile}.");
+    // Older API versions don't accept metrics, which shouldn't stop the
+    // scan from running.
+    if let Err(e) = amplify::submit_metrics(
+        endpoint.to_owned(),
+        amplify_token.to_owned(),
+        &code_metrics,
+        project_path,
+    )
+    .await
+    {
+        println!("::warning::Could not submit code metrics to Amplify: {e}");
+    }
+    ScanTarget {
+        ctx,
+        metadata,
+        profile,
+    }
+}
+
+fn enabled_str(enabled: bool) -> &'static str {
+    if enabled {
+        "enabled"
+    } else {
+        "disabled"
+    }
+}
@@ -0,0 +1,107 @@
+//! Code metrics for the scanned paths, computed with tokei.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tokei::{Config, Language, LanguageType, Languages};
+
+use crate::amplifyignore;
+
+/// Line and file counts for one language, or for all of them combined.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize)]
+pub struct LanguageMetrics {
+    pub code: usize,
+    pub comments: usize,
+    pub blanks: usize,
+    pub files: usize,
+}
+
+impl From<&Language> for LanguageMetrics {
+    fn from(language: &Language) -> Self {
+        LanguageMetrics {
+            code: language.code,
+            comments: language.comments,
+            blanks: language.blanks,
+            files: language.reports.len(),
+        }
+    }
+}
+
+/// Per-language breakdown of the code that was scanned.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CodeMetrics {
+    /// Metrics keyed by tokei's language name, e.g. `"Rust"`.
+    pub languages: BTreeMap<String, LanguageMetrics>,
+    pub total: LanguageMetrics,
+}
+
+impl CodeMetrics {
+    pub fn from_languages(languages: &Languages) -> Self {
+        let languages: BTreeMap<String, LanguageMetrics> = languages
+            .iter()
+            .map(|(language_type, language)| (language_type.name().to_owned(), language.into()))
+            .collect();
+        let total = languages
+            .values()
+            .fold(LanguageMetrics::default(), |total, language| {
+                LanguageMetrics {
+                    code: total.code + language.code,
+                    comments: total.comments + language.comments,
+                    blanks: total.blanks + language.blanks,
+                    files: total.files + language.files,
+                }
+            });
+        CodeMetrics { languages, total }
+    }
+}
+
+/// Count the code in `paths`, skipping anything in `.amplifyignore`.
+///
+/// The files are listed with [`amplifyignore::walker`] rather than by tokei,
+/// which doesn't know about `.amplifyignore` files.
+pub fn get_languages(paths: &[String]) -> Languages {
+    let config = Config::default();
+    let mut languages = Languages::new();
+    let files = amplifyignore::walker(paths)
+        .map(|walker| walker.build())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|kind| kind.is_file()));
+    for file in files {
+        let Some(language_type) = LanguageType::from_path(file.path(), &config) else {
+            continue;
+        };
+        let language = languages.entry(language_type).or_default();
+        match language_type.parse(file.path().to_path_buf(), &config) {
+            Ok(report) => language.add_report(report),
+            Err(_) => language.mark_inaccurate(),
+        }
+    }
+    for language in languages.values_mut() {
+        language.total();
+    }
+    languages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amplifyignore::AMPLIFYIGNORE_PATH;
+    use crate::common::test_support::TempDir;
+
+    #[test]
+    fn test_files_in_amplifyignore_are_not_counted() {
+        let root = TempDir::new("metrics");
+        std::fs::create_dir_all(root.join("web/dist")).unwrap();
+        std::fs::write(root.join("main.py"), "print(1)\n").unwrap();
+        std::fs::write(root.join("web/app.py"), "print(2)\n").unwrap();
+        std::fs::write(root.join("web/dist/bundle.py"), "print(3)\n").unwrap();
+        std::fs::write(root.join("web").join(AMPLIFYIGNORE_PATH), "dist/\n").unwrap();
+
+        let languages = get_languages(&[root.to_string_lossy().into_owned()]);
+
+        let python = &languages[&LanguageType::Python];
+        assert_eq!(python.reports.len(), 2);
+        assert_eq!(python.code, 2);
+    }
+}
@@ -0,0 +1,142 @@
+//! Project discovery for monorepos, where each project root is scanned and
+//! reported to Amplify separately.
+
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+
+use crate::amplifyignore;
+
+/// Files that mark the root of a project when no project list is configured.
+pub const DEFAULT_PROJECT_MARKERS: &[&str] = &[
+    "build.gradle",
+    "build.gradle.kts",
+    "Cargo.toml",
+    "composer.json",
+    "Gemfile",
+    "go.mod",
+    "package.json",
+    "pom.xml",
+    "pyproject.toml",
+    "requirements.txt",
+    "setup.py",
+];
+
+/// Find project roots under `paths` by looking for directories that contain
+/// one of `markers`.
+///
+/// Projects nested inside another project are treated as part of the outer
+/// one. A marker in a scan path itself, like a workspace manifest at the
+/// repository root, only counts when no other projects are found below it.
+pub fn discover_projects(paths: &[String], markers: &[String]) -> Vec<String> {
+    let Some(walker) = amplifyignore::walker(paths) else {
+        return Vec::new();
+    };
+
+    let mut roots = BTreeSet::new();
+    for entry in walker.build().filter_map(|entry| entry.ok()) {
+        let is_marker = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| markers.iter().any(|marker| marker == name));
+        if let (true, Some(parent)) = (is_marker, entry.path().parent()) {
+            roots.insert(normalize(parent));
+        }
+    }
+
+    let nested: BTreeSet<PathBuf> = roots
+        .iter()
+        .filter(|root| {
+            roots.iter().any(|other| {
+                other != *root && !is_scan_path(other, paths) && root.starts_with(other)
+            })
+        })
+        .cloned()
+        .collect();
+    let mut projects: Vec<&PathBuf> = roots
+        .iter()
+        .filter(|root| !nested.contains(*root) && !is_scan_path(root, paths))
+        .collect();
+    if projects.is_empty() {
+        projects = roots
+            .iter()
+            .filter(|root| !nested.contains(*root))
+            .collect();
+    }
+    projects
+        .into_iter()
+        .map(|root| root.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Strip `.` components so that `./a/b` and `a/b` compare equal. The
+/// working directory itself becomes `.`.
+fn normalize(path: &Path) -> PathBuf {
+    let normalized: PathBuf = path
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect();
+    if normalized.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        normalized
+    }
+}
+
+fn is_scan_path(root: &Path, paths: &[String]) -> bool {
+    paths.iter().any(|path| normalize(Path::new(path)) == root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_support::TempDir;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize(Path::new("./services/api")),
+            Path::new("services/api")
+        );
+        assert_eq!(normalize(Path::new(".")), Path::new("."));
+        assert_eq!(normalize(Path::new("")), Path::new("."));
+    }
+
+    #[test]
+    fn test_discover_projects_skips_root_and_nested_projects() {
+        let root = TempDir::new("monorepo");
+        for dir in ["services/api", "services/web/tools/gen", "docs"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "package.json",
+            "services/api/package.json",
+            "services/web/go.mod",
+            "services/web/tools/gen/go.mod",
+            "docs/index.md",
+        ] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+        let markers: Vec<String> = DEFAULT_PROJECT_MARKERS
+            .iter()
+            .map(|marker| marker.to_string())
+            .collect();
+
+        let projects = discover_projects(&[root.to_string_lossy().into_owned()], &markers);
+
+        assert_eq!(
+            projects,
+            vec![
+                root.join("services/api").to_string_lossy(),
+                root.join("services/web").to_string_lossy()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_scan_path() {
+        let paths = vec![".".to_owned(), "./services".to_owned()];
+        assert!(is_scan_path(Path::new("."), &paths));
+        assert!(is_scan_path(Path::new("services"), &paths));
+        assert!(!is_scan_path(Path::new("services/api"), &paths));
+    }
+}
@@ -0,0 +1,108 @@
+//! Summary of what a repository contains, used to decide which tools are
+//! worth running against it.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::PathBuf;
+use tokei::{LanguageType, Languages};
+
+use crate::amplifyignore;
+
+/// File names of dependency lockfiles for the package managers we know of.
+const LOCKFILE_NAMES: &[&str] = &[
+    "Cargo.lock",
+    "composer.lock",
+    "Gemfile.lock",
+    "go.sum",
+    "gradle.lockfile",
+    "package-lock.json",
+    "packages.lock.json",
+    "Pipfile.lock",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "uv.lock",
+    "yarn.lock",
+];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepoProfile {
+    /// Languages with at least one file in the scanned paths.
+    pub languages: BTreeSet<LanguageType>,
+    /// Infrastructure-as-code files, such as Terraform.
+    pub iac_files: Vec<PathBuf>,
+    /// Dependency lockfiles.
+    pub lockfiles: Vec<PathBuf>,
+    pub dockerfiles: Vec<PathBuf>,
+}
+
+impl RepoProfile {
+    /// Build a profile from tokei's statistics for the scanned paths, plus a
+    /// walk of `paths` for files that tokei doesn't count, like lockfiles.
+    pub fn new(languages: &Languages, paths: &[String]) -> Self {
+        let files_of = |language_type: LanguageType| -> Vec<PathBuf> {
+            languages
+                .get(&language_type)
+                .map(|language| {
+                    language
+                        .reports
+                        .iter()
+                        .map(|report| report.name.clone())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        RepoProfile {
+            languages: languages
+                .iter()
+                .filter(|(_, language)| !language.reports.is_empty())
+                .map(|(language_type, _)| *language_type)
+                .collect(),
+            iac_files: files_of(LanguageType::Hcl),
+            lockfiles: find_lockfiles(paths),
+            dockerfiles: files_of(LanguageType::Dockerfile),
+        }
+    }
+
+    /// Whether any of `languages` is present in the repository.
+    pub fn has_any_language(&self, languages: &[LanguageType]) -> bool {
+        languages
+            .iter()
+            .any(|language| self.languages.contains(language))
+    }
+}
+
+impl fmt::Display for RepoProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let languages: Vec<&str> = self
+            .languages
+            .iter()
+            .map(|language| language.name())
+            .collect();
+        write!(
+            f,
+            "languages: [{}], IaC files: {}, lockfiles: {}, Dockerfiles: {}",
+            languages.join(", "),
+            self.iac_files.len(),
+            self.lockfiles.len(),
+            self.dockerfiles.len()
+        )
+    }
+}
+
+fn find_lockfiles(paths: &[String]) -> Vec<PathBuf> {
+    let Some(walker) = amplifyignore::walker(paths) else {
+        return Vec::new();
+    };
+    walker
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| LOCKFILE_NAMES.contains(&name))
+        })
+        .map(|entry| entry.path().to_path_buf())
+        .collect()
+}
@@ -0,0 +1,580 @@
+//! Runs tools against scan targets concurrently.
+//!
+//! Every tool that applies to at least one target is installed up front, all
+//! at the same time. Scans then run in parallel, at most `jobs` at once, and
+//! each artifact is uploaded as soon as its scan finishes. Tool output is
+//! buffered per scan and printed as one group when the scan is done, so the
+//! CI log stays readable however the scans interleave.
+//!
+//! What happens when a tool fails is decided by the [`FailurePolicy`], and
+//! the outcome of every scan is collected into a [`RunSummary`]. A tool that
+//! runs out of time counts as a failure, but is reported separately. When
+//! the runner is asked to stop, every task still in flight is cancelled, and
+//! the summary covers what finished before that.
+
+use color_eyre::eyre::Result;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::{Id, JoinSet};
+use tokio::time::Instant;
+
+use crate::amplify::{self, ArtifactMetadata};
+use crate::common::ToolLog;
+use crate::profile::RepoProfile;
+use crate::shutdown::Shutdown;
+use crate::tools::opengrep::report::{Report, Severity};
+use crate::tools::{RunContext, Tool, ToolActions, ToolError};
+
+/// Paths to scan and report to Amplify as one artifact per tool: either the
+/// whole repository or one project of a monorepo.
+#[derive(Debug, Clone)]
+pub struct ScanTarget {
+    pub ctx: RunContext,
+    pub metadata: ArtifactMetadata,
+    pub profile: RepoProfile,
+}
+
+impl ScanTarget {
+    /// Title for a tool's log group when it scans this target.
+    fn log_title(&self, tool: &Tool) -> String {
+        match &self.metadata.project_path {
+            Some(project_path) => format!("{} scan ({project_path})", tool.name()),
+            None => format!("{} scan", tool.name()),
+        }
+    }
+}
+
+/// How the run reacts when a tool fails to install, scan or upload.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailurePolicy {
+    /// Stop every other tool at the first failure and fail the run.
+    #[default]
+    FailFast,
+    /// Keep running the other tools, then fail the run if any tool failed.
+    Continue,
+    /// Keep running the other tools, and only fail the run if every scan
+    /// failed.
+    BestEffort,
+}
+
+impl FromStr for FailurePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fail-fast" => Ok(Self::FailFast),
+            "continue" => Ok(Self::Continue),
+            "best-effort" => Ok(Self::BestEffort),
+            _ => Err(format!(
+                "Unknown failure policy `{s}`, expected fail-fast, continue or best-effort."
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FailurePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FailFast => write!(f, "fail-fast"),
+            Self::Continue => write!(f, "continue"),
+            Self::BestEffort => write!(f, "best-effort"),
+        }
+    }
+}
+
+/// What happened to one tool's scan of one target.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    /// The scan finished and its artifact was uploaded.
+    Succeeded,
+    /// The tool doesn't apply to the target.
+    Skipped(String),
+    /// Installing, scanning or uploading failed.
+    Failed(String),
+    /// The tool ran past its timeout or the run's, and was stopped.
+    TimedOut(String),
+    /// The scan was stopped, or never started, because another tool failed
+    /// or the runner was asked to stop.
+    Cancelled,
+}
+
+impl Status {
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Status::Failed(_) | Status::TimedOut(_))
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Succeeded => write!(f, "succeeded"),
+            Status::Skipped(reason) => write!(f, "skipped: {reason}"),
+            Status::Failed(error) => write!(f, "failed: {error}"),
+            Status::TimedOut(error) => write!(f, "timed out: {error}"),
+            Status::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub tool: &'static str,
+    /// Project within a monorepo, or `None` for the whole repository.
+    pub project_path: Option<String>,
+    pub status: Status,
+    /// What the scan found, if it finished and the tool's output is
+    /// understood by the runner.
+    pub findings: Option<Report>,
+    /// How long the tool ran for, if it was started.
+    pub duration: Option<Duration>,
+}
+
+/// Outcome of every scan in a run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunSummary {
+    pub outcomes: Vec<Outcome>,
+    /// Lines of code across every scan target.
+    pub code_lines: usize,
+}
+
+impl RunSummary {
+    fn count(&self, matches: impl Fn(&Status) -> bool) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|outcome| matches(&outcome.status))
+            .count()
+    }
+
+    /// Number of findings across every scan that are at least as severe as
+    /// `threshold`.
+    pub fn findings_at_least(&self, threshold: Severity) -> usize {
+        self.outcomes
+            .iter()
+            .filter_map(|outcome| outcome.findings.as_ref())
+            .flat_map(|report| &report.results)
+            .filter(|finding| finding.extra.severity >= threshold)
+            .count()
+    }
+
+    /// Whether the run as a whole succeeded under `policy`.
+    pub fn succeeded(&self, policy: FailurePolicy) -> bool {
+        let failed = self.count(Status::is_failure);
+        match policy {
+            FailurePolicy::FailFast | FailurePolicy::Continue => failed == 0,
+            FailurePolicy::BestEffort => {
+                failed == 0 || self.count(|status| *status == Status::Succeeded) > 0
+            }
+        }
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Run summary: {} succeeded, {} failed, {} timed out, {} cancelled, {} skipped.",
+            self.count(|status| *status == Status::Succeeded),
+            self.count(|status| matches!(status, Status::Failed(_))),
+            self.count(|status| matches!(status, Status::TimedOut(_))),
+            self.count(|status| *status == Status::Cancelled),
+            self.count(|status| matches!(status, Status::Skipped(_))),
+        )?;
+        for outcome in &self.outcomes {
+            match &outcome.project_path {
+                Some(project_path) => write!(f, "  {} ({project_path})", outcome.tool)?,
+                None => write!(f, "  {}", outcome.tool)?,
+            }
+            writeln!(f, ": {}", outcome.status)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Scheduler {
+    endpoint: String,
+    token: String,
+    /// Maximum number of scans to run at the same time.
+    jobs: usize,
+    policy: FailurePolicy,
+    shutdown: Shutdown,
+}
+
+impl Scheduler {
+    pub fn new(
+        endpoint: String,
+        token: String,
+        jobs: usize,
+        policy: FailurePolicy,
+        shutdown: Shutdown,
+    ) -> Self {
+        Scheduler {
+            endpoint,
+            token,
+            jobs: jobs.max(1),
+            policy,
+            shutdown,
+        }
+    }
+
+    /// Install `tools`, run each one against every target it applies to and
+    /// upload the results.
+    pub async fn run(
+        &self,
+        ctx: &RunContext,
+        tools: Vec<Tool>,
+        targets: Vec<ScanTarget>,
+    ) -> RunSummary {
+        self.shutdown.take_over();
+        let tools: Vec<Arc<Tool>> = tools.into_iter().map(Arc::new).collect();
+        let targets: Vec<Arc<ScanTarget>> = targets.into_iter().map(Arc::new).collect();
+
+        let mut summary = RunSummary {
+            code_lines: targets
+                .iter()
+                .map(|target| target.metadata.code_lines)
+                .sum(),
+            ..RunSummary::default()
+        };
+        let mut scans = Vec::new();
+        for target in &targets {
+            for (index, tool) in tools.iter().enumerate() {
+                match tool.skip_reason(&target.profile) {
+                    Some(reason) => {
+                        println!("Skipping {}: {reason}", target.log_title(tool));
+                        summary.outcomes.push(Outcome {
+                            tool: tool.name(),
+                            project_path: target.metadata.project_path.clone(),
+                            status: Status::Skipped(reason),
+                            findings: None,
+                            duration: None,
+                        });
+                    }
+                    None => scans.push((index, target.clone())),
+                }
+            }
+        }
+        let used: BTreeSet<usize> = scans.iter().map(|(index, _)| *index).collect();
+
+        let mut installs = Tasks::default();
+        for &index in &used {
+            let tool = tools[index].clone();
+            let ctx = ctx.clone();
+            let title = format!("{} install", tool.name());
+            installs.spawn(index, title, |log| async move {
+                before_deadline(ctx.deadline, tool.name(), tool.setup(&ctx, &log)).await
+            });
+        }
+        let installed = installs.join_all(self.policy, &self.shutdown).await;
+        let install_failed = installed.values().any(Status::is_failure);
+
+        let permits = Arc::new(Semaphore::new(self.jobs));
+        let findings = Arc::new(Mutex::new(BTreeMap::new()));
+        let durations = Arc::new(Mutex::new(BTreeMap::new()));
+        let mut launches = Tasks::default();
+        let mut statuses = BTreeMap::new();
+        for (scan, (index, target)) in scans.iter().enumerate() {
+            let tool = tools[*index].clone();
+            match &installed[index] {
+                Status::Failed(error) => {
+                    statuses.insert(scan, Status::Failed(format!("install failed: {error}")));
+                    continue;
+                }
+                Status::TimedOut(error) => {
+                    statuses.insert(scan, Status::TimedOut(format!("install: {error}")));
+                    continue;
+                }
+                Status::Cancelled => {
+                    statuses.insert(scan, Status::Cancelled);
+                    continue;
+                }
+                _ if self.shutdown.received().is_some()
+                    || (install_failed && self.policy == FailurePolicy::FailFast) =>
+                {
+                    statuses.insert(scan, Status::Cancelled);
+                    continue;
+                }
+                _ => {}
+            }
+            let target = target.clone();
+            let permits = permits.clone();
+            let endpoint = self.endpoint.clone();
+            let token = self.token.clone();
+            let findings = findings.clone();
+            let durations = durations.clone();
+            let title = target.log_title(&tool);
+            launches.spawn(scan, title, |log| async move {
+                let permit = permits.acquire_owned().await?;
+                let started = Instant::now();
+                let launched = tool.launch(&target.ctx, &log).await;
+                durations.lock().unwrap().insert(scan, started.elapsed());
+                // Uploading doesn't need a job slot, so the next scan can
+                // start in the meantime.
+                drop(permit);
+                let mut artifact = launched?;
+                if let Some(report) = artifact.findings.take() {
+                    findings.lock().unwrap().insert(scan, report);
+                }
+                amplify::submit_artifact(endpoint, token, artifact, &target.metadata).await?;
+                log.println("Successfully submitted tool result to Amplify.");
+                Ok(())
+            });
+        }
+        statuses.extend(launches.join_all(self.policy, &self.shutdown).await);
+
+        for (scan, (index, target)) in scans.iter().enumerate() {
+            summary.outcomes.push(Outcome {
+                tool: tools[*index].name(),
+                project_path: target.metadata.project_path.clone(),
+                status: statuses.remove(&scan).unwrap_or(Status::Cancelled),
+                findings: findings.lock().unwrap().remove(&scan),
+                duration: durations.lock().unwrap().remove(&scan),
+            });
+        }
+        summary
+    }
+}
+
+/// Run `task`, giving up on it if the run's `deadline` passes first.
+async fn before_deadline<T>(
+    deadline: Option<Instant>,
+    tool: &str,
+    task: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let Some(deadline) = deadline else {
+        return task.await;
+    };
+    let limit = deadline.saturating_duration_since(Instant::now());
+    tokio::time::timeout_at(deadline, task)
+        .await
+        .unwrap_or_else(|_| {
+            Err(ToolError::TimedOut {
+                tool: tool.to_owned(),
+                limit,
+                stderr: String::new(),
+            }
+            .into())
+        })
+}
+
+/// Tool tasks that each buffer their own log, keyed by `K`.
+struct Tasks<K> {
+    set: JoinSet<Result<()>>,
+    keys: HashMap<Id, (K, String, Arc<ToolLog>)>,
+}
+
+impl<K> Default for Tasks<K> {
+    fn default() -> Self {
+        Tasks {
+            set: JoinSet::new(),
+            keys: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Ord> Tasks<K> {
+    /// Spawn the task that `task` returns when given the log to write to.
+    fn spawn<F, T>(&mut self, key: K, title: String, task: F)
+    where
+        F: FnOnce(Arc<ToolLog>) -> T,
+        T: Future<Output = Result<()>> + Send + 'static,
+    {
+        let log = Arc::new(ToolLog::default());
+        let handle = self.set.spawn(task(log.clone()));
+        self.keys.insert(handle.id(), (key, title, log));
+    }
+
+    /// Wait for every task, printing each one's log as it finishes. With
+    /// [`FailurePolicy::FailFast`], the first failure aborts the rest, and
+    /// every task is aborted once `shutdown` is triggered.
+    async fn join_all(mut self, policy: FailurePolicy, shutdown: &Shutdown) -> BTreeMap<K, Status> {
+        let mut statuses = BTreeMap::new();
+        let mut stopping = false;
+        loop {
+            let task = tokio::select! {
+                task = self.set.join_next_with_id() => match task {
+                    Some(task) => task,
+                    None => break,
+                },
+                _ = shutdown.wait(), if !stopping => {
+                    stopping = true;
+                    self.set.abort_all();
+                    continue;
+                }
+            };
+            let id = match &task {
+                Ok((id, _)) => *id,
+                Err(e) => e.id(),
+            };
+            let title = &self.keys[&id].1;
+            self.keys[&id].2.flush(title);
+            let status = match task {
+                Ok((_, result)) => match result {
+                    Ok(()) => Status::Succeeded,
+                    Err(e) if matches!(e.downcast_ref(), Some(ToolError::TimedOut { .. })) => {
+                        println!("::error::{title} timed out: {e:#}");
+                        Status::TimedOut(format!("{e:#}"))
+                    }
+                    Err(e) => {
+                        println!("::error::{title} failed: {e:#}");
+                        Status::Failed(format!("{e:#}"))
+                    }
+                },
+                Err(e) if e.is_cancelled() => Status::Cancelled,
+                Err(e) => {
+                    println!("::error::{title} panicked: {e}");
+                    Status::Failed(format!("panicked: {e}"))
+                }
+            };
+            if status.is_failure() && policy == FailurePolicy::FailFast {
+                self.set.abort_all();
+            }
+            if let Some((key, _, _)) = self.keys.remove(&id) {
+                statuses.insert(key, status);
+            }
+        }
+        statuses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_support::summary;
+
+    #[test]
+    fn test_failure_policy_from_str() {
+        assert_eq!("fail-fast".parse(), Ok(FailurePolicy::FailFast));
+        assert_eq!("continue".parse(), Ok(FailurePolicy::Continue));
+        assert_eq!("best-effort".parse(), Ok(FailurePolicy::BestEffort));
+        assert!("sometimes".parse::<FailurePolicy>().is_err());
+    }
+
+    #[test]
+    fn test_run_succeeds_without_failures() {
+        let run = summary(
+            &[Status::Succeeded, Status::Skipped("no files".into())],
+            None,
+        );
+        assert!(run.succeeded(FailurePolicy::FailFast));
+        assert!(run.succeeded(FailurePolicy::Continue));
+        assert!(run.succeeded(FailurePolicy::BestEffort));
+        assert!(RunSummary::default().succeeded(FailurePolicy::Continue));
+    }
+
+    #[test]
+    fn test_best_effort_only_fails_when_nothing_succeeded() {
+        let partial = summary(
+            &[Status::Succeeded, Status::Failed("timed out".into())],
+            None,
+        );
+        assert!(!partial.succeeded(FailurePolicy::FailFast));
+        assert!(!partial.succeeded(FailurePolicy::Continue));
+        assert!(partial.succeeded(FailurePolicy::BestEffort));
+
+        let failed = summary(
+            &[Status::Failed("timed out".into()), Status::Cancelled],
+            None,
+        );
+        assert!(!failed.succeeded(FailurePolicy::BestEffort));
+    }
+
+    #[test]
+    fn test_summary_display() {
+        let mut run = summary(&[Status::Succeeded], None);
+        run.outcomes.push(Outcome {
+            tool: "uname",
+            project_path: Some("services/api".into()),
+            status: Status::Failed("exit code 2".into()),
+            findings: None,
+            duration: None,
+        });
+        assert_eq!(
+            run.to_string(),
+            "Run summary: 1 succeeded, 1 failed, 0 timed out, 0 cancelled, 0 skipped.\n  opengrep: succeeded\n  uname (services/api): failed: exit code 2\n"
+        );
+    }
+
+    #[test]
+    fn test_timeouts_count_as_failures() {
+        let run = summary(
+            &[Status::Succeeded, Status::TimedOut("opengrep".into())],
+            None,
+        );
+        assert!(!run.succeeded(FailurePolicy::Continue));
+        assert!(run.succeeded(FailurePolicy::BestEffort));
+        assert!(run.to_string().contains("0 failed, 1 timed out"));
+    }
+
+    #[test]
+    fn test_findings_at_least() {
+        let run = summary(
+            &[Status::Succeeded],
+            Some(
+                r#"{"results":[{"extra":{"severity":"ERROR"}},{"extra":{"severity":"WARNING"}},{"extra":{"severity":"INVENTORY"}}]}"#,
+            ),
+        );
+
+        assert_eq!(run.findings_at_least(Severity::Critical), 0);
+        assert_eq!(run.findings_at_least(Severity::High), 1);
+        assert_eq!(run.findings_at_least(Severity::Low), 2);
+    }
+
+    #[tokio::test]
+    async fn test_install_stops_at_the_deadline() {
+        let deadline = Instant::now() + std::time::Duration::from_millis(50);
+        let result: Result<()> = before_deadline(Some(deadline), "opengrep", async {
+            std::future::pending::<()>().await;
+            Ok(())
+        })
+        .await;
+        assert!(result.unwrap_err().downcast_ref::<ToolError>().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_fail_fast_cancels_remaining_tasks() {
+        let mut tasks = Tasks::default();
+        tasks.spawn(0, "fails".into(), |_| async {
+            Err(color_eyre::eyre::eyre!("boom"))
+        });
+        tasks.spawn(1, "hangs".into(), |_| async {
+            std::future::pending::<()>().await;
+            Ok(())
+        });
+
+        let statuses = tasks
+            .join_all(FailurePolicy::FailFast, &Shutdown::default())
+            .await;
+
+        assert_eq!(statuses[&0], Status::Failed("boom".into()));
+        assert_eq!(statuses[&1], Status::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_cancels_running_tasks() {
+        let shutdown = Shutdown::default();
+        let mut tasks = Tasks::default();
+        tasks.spawn(0, "succeeds".into(), |_| async { Ok(()) });
+        tasks.spawn(1, "hangs".into(), |log| async move {
+            log.println("Scanning.");
+            std::future::pending::<()>().await;
+            Ok(())
+        });
+        let trigger = shutdown.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            trigger.trigger(crate::shutdown::Signal::Terminate);
+        });
+
+        let statuses = tasks.join_all(FailurePolicy::Continue, &shutdown).await;
+
+        assert_eq!(statuses[&0], Status::Succeeded);
+        assert_eq!(statuses[&1], Status::Cancelled);
+    }
+}
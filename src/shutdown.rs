@@ -0,0 +1,165 @@
+//! Stopping the run when the CI job is cancelled.
+//!
+//! CI systems cancel a job by sending `SIGTERM`, and a local run is stopped
+//! with `SIGINT`. Until the scans start, the run is simply abandoned. Once
+//! they have started, the scheduler takes over: it cancels every install,
+//! scan and upload that is still going, which kills the tools' processes,
+//! and the partial run summary is printed before the runner exits. A second
+//! signal kills any tools that are still running and exits straight away.
+
+use color_eyre::eyre::{Result, WrapErr};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+
+use crate::tools::process;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Signal {
+    Interrupt,
+    Terminate,
+}
+
+impl Signal {
+    /// Exit code for a process stopped by this signal: 128 plus the signal
+    /// number, as shells report it.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Signal::Interrupt => 130,
+            Signal::Terminate => 143,
+        }
+    }
+}
+
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Signal::Interrupt => write!(f, "SIGINT"),
+            Signal::Terminate => write!(f, "SIGTERM"),
+        }
+    }
+}
+
+/// Whether the runner has been asked to stop, shared by everything that
+/// needs to react to it.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    signal: Arc<watch::Sender<Option<Signal>>>,
+    taken_over: Arc<AtomicBool>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Shutdown {
+            signal: Arc::new(watch::Sender::new(None)),
+            taken_over: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl Shutdown {
+    /// Start listening for `SIGINT` and `SIGTERM`.
+    pub fn listen() -> Result<Shutdown> {
+        let shutdown = Shutdown::default();
+        let mut interrupt =
+            signal(SignalKind::interrupt()).wrap_err("Failed to listen for SIGINT.")?;
+        let mut terminate =
+            signal(SignalKind::terminate()).wrap_err("Failed to listen for SIGTERM.")?;
+        let listener = shutdown.clone();
+        tokio::spawn(async move {
+            loop {
+                let signal = tokio::select! {
+                    _ = interrupt.recv() => Signal::Interrupt,
+                    _ = terminate.recv() => Signal::Terminate,
+                };
+                if listener.received().is_some() {
+                    println!("Received {signal} again, exiting now.");
+                    process::kill_running();
+                    std::process::exit(signal.exit_code().into());
+                }
+                println!("::warning::Received {signal}, stopping the run.");
+                listener.trigger(signal);
+            }
+        });
+        Ok(shutdown)
+    }
+
+    pub fn trigger(&self, signal: Signal) {
+        self.signal.send_replace(Some(signal));
+    }
+
+    /// The signal that stopped the run, if any.
+    pub fn received(&self) -> Option<Signal> {
+        *self.signal.borrow()
+    }
+
+    /// Wait until the runner is asked to stop.
+    pub async fn wait(&self) -> Signal {
+        let mut receiver = self.signal.subscribe();
+        loop {
+            if let Some(signal) = *receiver.borrow_and_update() {
+                return signal;
+            }
+            // The sender lives as long as `self`, so this can't fail.
+            let _ = receiver.changed().await;
+        }
+    }
+
+    /// Handle signals from here on by cancelling the run's work rather than
+    /// abandoning the run, so that [`Shutdown::before_take_over`] no longer
+    /// resolves.
+    pub fn take_over(&self) {
+        self.taken_over.store(true, Ordering::SeqCst);
+    }
+
+    /// Wait until the runner is asked to stop before something has called
+    /// [`Shutdown::take_over`].
+    pub async fn before_take_over(&self) -> Signal {
+        let signal = self.wait().await;
+        if self.taken_over.load(Ordering::SeqCst) {
+            std::future::pending::<()>().await;
+        }
+        signal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_wait_returns_the_signal() {
+        let shutdown = Shutdown::default();
+        assert_eq!(shutdown.received(), None);
+
+        let waiter = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.wait().await }
+        });
+        shutdown.trigger(Signal::Terminate);
+
+        assert_eq!(waiter.await.unwrap(), Signal::Terminate);
+        assert_eq!(shutdown.received(), Some(Signal::Terminate));
+        assert_eq!(shutdown.wait().await, Signal::Terminate);
+    }
+
+    #[tokio::test]
+    async fn test_take_over_stops_before_take_over() {
+        let shutdown = Shutdown::default();
+        shutdown.take_over();
+        shutdown.trigger(Signal::Interrupt);
+
+        let result =
+            tokio::time::timeout(Duration::from_millis(50), shutdown.before_take_over()).await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(Signal::Interrupt.exit_code(), 130);
+        assert_eq!(Signal::Terminate.exit_code(), 143);
+    }
+}
@@ -0,0 +1,90 @@
+//! Code scanners that the runner can install and launch.
+
+pub(crate) mod opengrep;
+pub(crate) mod process;
+pub(crate) mod uname;
+
+use color_eyre::eyre::Result;
+use enum_dispatch::enum_dispatch;
+use std::sync::Arc;
+use tokio::time::Instant;
+
+use crate::amplify::{Artifact, ProjectSettings, ToolConfig, Tools};
+use crate::amplifyignore::AmplifyIgnore;
+use crate::cache::ToolCache;
+use crate::common::ToolLog;
+use crate::profile::RepoProfile;
+use crate::workdir::WorkDir;
+
+pub use opengrep::Opengrep;
+pub use process::ToolError;
+pub use uname::Uname;
+
+/// State for a single runner invocation that is shared with every tool.
+#[derive(Debug, Clone, Default)]
+pub struct RunContext {
+    pub settings: ProjectSettings,
+    /// Paths to scan, relative to the working directory.
+    pub paths: Vec<String>,
+    /// Project of a monorepo that the paths belong to, if any.
+    pub project_path: Option<String>,
+    /// Paths that must not be scanned, from `.amplifyignore`.
+    pub ignore: AmplifyIgnore,
+    /// Where downloaded tools are kept between runs.
+    pub cache: ToolCache,
+    /// Where tools and their intermediate files are written during the run.
+    pub work_dir: Arc<WorkDir>,
+    /// Commit to compare against so that only new findings are reported,
+    /// or `None` to report every finding.
+    pub baseline_commit: Option<String>,
+    /// When the whole run has to be finished by, from the run timeout.
+    pub deadline: Option<Instant>,
+    /// Largest report that a tool may produce, or `None` for no limit.
+    pub max_artifact_bytes: Option<u64>,
+}
+
+impl RunContext {
+    /// Name to print for `tool` when it runs in this context, so that the
+    /// output of each project's scan can be told apart.
+    pub fn tool_label(&self, tool: &str) -> String {
+        match &self.project_path {
+            Some(project_path) => format!("{tool} {project_path}"),
+            None => tool.to_owned(),
+        }
+    }
+}
+
+/// Tool versions in the cache that this runner still uses, as
+/// `(tool, version)` pairs.
+pub fn cache_entries() -> Vec<(&'static str, &'static str)> {
+    opengrep::CACHE_ENTRIES.to_vec()
+}
+
+#[enum_dispatch(Tool)]
+pub trait ToolActions {
+    /// Name of the tool in log output.
+    fn name(&self) -> &'static str;
+    /// Why the tool has nothing to do in a repository, or `None` if it
+    /// should run.
+    fn skip_reason(&self, profile: &RepoProfile) -> Option<String>;
+    async fn setup(&self, ctx: &RunContext, log: &ToolLog) -> Result<()>;
+    async fn launch(&self, ctx: &RunContext, log: &ToolLog) -> Result<Artifact>;
+}
+
+#[enum_dispatch]
+pub enum Tool {
+    Opengrep,
+    Uname,
+}
+
+impl Tool {
+    pub fn new_from(tool: ToolConfig) -> Tool {
+        let options = tool.options;
+        match tool.name {
+            // Map Semgrep from API to Opengrep. May change later depending on
+            // if Amplify's API retroactively renames the tool for everyone.
+            Tools::Semgrep => Tool::Opengrep(Opengrep::new(options)),
+            Tools::Uname => Tool::Uname(Uname::new(options)),
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! Opengrep release assets for each platform the runner supports.
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use hex_literal::hex;
+use std::fmt;
+use std::path::Path;
+
+use crate::common::ToolLog;
+
+/// Hex-encoded SHA-256 of the Opengrep asset to trust instead of the pinned
+/// one, e.g. for a mirrored or patched build.
+const ENV_OPENGREP_SHA256: &str = "AMPLIFY_OPENGREP_SHA256";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Arch {
+    X86_64,
+    Aarch64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Libc {
+    Glibc,
+    Musl,
+}
+
+/// A Linux host that Opengrep publishes a binary for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Platform {
+    pub arch: Arch,
+    pub libc: Libc,
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arch = match self.arch {
+            Arch::X86_64 => "x86_64",
+            Arch::Aarch64 => "aarch64",
+        };
+        let libc = match self.libc {
+            Libc::Glibc => "glibc",
+            Libc::Musl => "musl",
+        };
+        write!(f, "linux/{arch} ({libc})")
+    }
+}
+
+impl Platform {
+    /// Detect the platform the runner is running on.
+    pub fn detect() -> Result<Platform> {
+        Self::from_parts(
+            std::env::consts::OS,
+            std::env::consts::ARCH,
+            Path::new("/lib"),
+        )
+    }
+
+    /// Platform for an OS and architecture as named by `std::env::consts`,
+    /// with the libc detected from the dynamic loaders in `lib_dir`.
+    fn from_parts(os: &str, arch: &str, lib_dir: &Path) -> Result<Platform> {
+        if os != "linux" {
+            return Err(eyre!(
+                "Opengrep can only be installed on Linux, but this runner is on {os}/{arch}."
+            ));
+        }
+        let arch = match arch {
+            "x86_64" => Arch::X86_64,
+            "aarch64" => Arch::Aarch64,
+            _ => {
+                return Err(eyre!(
+                    "Opengrep is not available for {os}/{arch}. Supported architectures are x86_64 and aarch64."
+                ))
+            }
+        };
+        Ok(Platform {
+            arch,
+            libc: detect_libc(lib_dir),
+        })
+    }
+}
+
+/// musl installs its dynamic loader as `ld-musl-<arch>.so.1`; anything else
+/// is assumed to be glibc.
+fn detect_libc(lib_dir: &Path) -> Libc {
+    let has_musl_loader = std::fs::read_dir(lib_dir).is_ok_and(|entries| {
+        entries.filter_map(|entry| entry.ok()).any(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.starts_with("ld-musl-"))
+        })
+    });
+    if has_musl_loader {
+        Libc::Musl
+    } else {
+        Libc::Glibc
+    }
+}
+
+/// An Opengrep binary published on GitHub releases.
+#[derive(Debug, PartialEq)]
+pub struct Asset {
+    pub platform: Platform,
+    /// File name of the asset in the release.
+    pub name: &'static str,
+    /// Expected SHA-256 of the asset, if one is pinned for this release.
+    pub checksum: Option<[u8; 32]>,
+}
+
+/// Every Opengrep asset the runner knows how to install. Checksums are
+/// pinned from the release that matches `OPENGREP_VERSION`, and must be
+/// updated along with it.
+pub const ASSETS: &[Asset] = &[
+    Asset {
+        platform: Platform {
+            arch: Arch::X86_64,
+            libc: Libc::Musl,
+        },
+        name: "opengrep_musllinux_x86",
+        checksum: Some(hex!(
+            "888022d917da27f568113073a93bdfb2dd222f7095fa689f012d96819279a611"
+        )),
+    },
+    Asset {
+        platform: Platform {
+            arch: Arch::X86_64,
+            libc: Libc::Glibc,
+        },
+        name: "opengrep_manylinux_x86",
+        checksum: None,
+    },
+    Asset {
+        platform: Platform {
+            arch: Arch::Aarch64,
+            libc: Libc::Musl,
+        },
+        name: "opengrep_musllinux_aarch64",
+        checksum: None,
+    },
+    Asset {
+        platform: Platform {
+            arch: Arch::Aarch64,
+            libc: Libc::Glibc,
+        },
+        name: "opengrep_manylinux_aarch64",
+        checksum: None,
+    },
+];
+
+impl Asset {
+    /// The asset to install on `platform`.
+    pub fn for_platform(platform: Platform) -> Result<&'static Asset> {
+        ASSETS
+            .iter()
+            .find(|asset| asset.platform == platform)
+            .ok_or_else(|| eyre!("No Opengrep binary is available for {platform}."))
+    }
+
+    /// The checksum to verify the downloaded asset against: the pinned one,
+    /// or for an asset without one, the one from `AMPLIFY_OPENGREP_SHA256`.
+    /// A pinned checksum can't be replaced.
+    pub fn expected_checksum(&self, log: &ToolLog) -> Result<[u8; 32]> {
+        let value = std::env::var(ENV_OPENGREP_SHA256).ok();
+        if let Some(checksum) = self.checksum {
+            if value.is_some() {
+                log.println(format!(
+                    "::notice::Ignoring {ENV_OPENGREP_SHA256}, since {} has a pinned checksum.",
+                    self.name
+                ));
+            }
+            return Ok(checksum);
+        }
+        let value = value.ok_or_else(|| {
+            eyre!(
+                "This runner has no pinned checksum for {} on {}. Set {ENV_OPENGREP_SHA256} to the SHA-256 published with the Opengrep release to install it.",
+                self.name,
+                self.platform
+            )
+        })?;
+        const_hex::decode_to_array(value.trim())
+            .wrap_err_with(|| format!("{ENV_OPENGREP_SHA256} is not a hex-encoded SHA-256."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_support::TempDir;
+
+    #[test]
+    fn test_every_platform_has_one_asset() {
+        for arch in [Arch::X86_64, Arch::Aarch64] {
+            for libc in [Libc::Glibc, Libc::Musl] {
+                let platform = Platform { arch, libc };
+                let matching = ASSETS
+                    .iter()
+                    .filter(|asset| asset.platform == platform)
+                    .count();
+                assert_eq!(matching, 1, "{platform}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_unsupported_platforms_are_errors() {
+        let lib_dir = Path::new("/nonexistent");
+        assert!(Platform::from_parts("macos", "aarch64", lib_dir).is_err());
+        assert!(Platform::from_parts("linux", "riscv64", lib_dir).is_err());
+        assert_eq!(
+            Platform::from_parts("linux", "aarch64", lib_dir).unwrap(),
+            Platform {
+                arch: Arch::Aarch64,
+                libc: Libc::Glibc
+            }
+        );
+    }
+
+    #[test]
+    fn test_detect_libc() {
+        let lib_dir = TempDir::new("libc");
+        assert_eq!(detect_libc(&lib_dir), Libc::Glibc);
+
+        std::fs::write(lib_dir.join("ld-musl-aarch64.so.1"), "").unwrap();
+        assert_eq!(detect_libc(&lib_dir), Libc::Musl);
+    }
+
+    #[tokio::test]
+    async fn test_checksum_override() {
+        let _lock = crate::common::test_support::ENV_MUTEX.lock().await;
+        let log = ToolLog::default();
+        let pinned = Asset::for_platform(Platform {
+            arch: Arch::X86_64,
+            libc: Libc::Musl,
+        })
+        .unwrap();
+
+        std::env::remove_var(ENV_OPENGREP_SHA256);
+        assert_eq!(
+            pinned.expected_checksum(&log).unwrap(),
+            pinned.checksum.unwrap()
+        );
+        assert!(log.lines().is_empty());
+
+        std::env::set_var(ENV_OPENGREP_SHA256, "ab".repeat(32));
+        assert_eq!(
+            pinned.expected_checksum(&log).unwrap(),
+            pinned.checksum.unwrap()
+        );
+        assert_eq!(log.lines().len(), 1);
+
+        let unpinned = Asset {
+            checksum: None,
+            ..*pinned
+        };
+        assert_eq!(unpinned.expected_checksum(&log).unwrap(), [0xab; 32]);
+        std::env::remove_var(ENV_OPENGREP_SHA256);
+        assert!(unpinned.expected_checksum(&log).is_err());
+    }
+}
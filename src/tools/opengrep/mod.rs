@@ -0,0 +1,643 @@
+//! Opengrep, an open source fork of Semgrep, run with Amplify's ruleset.
+
+mod assets;
+pub(crate) mod report;
+pub(crate) mod rules;
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::sync::OnceLock;
+use tokei::LanguageType;
+use tokio::process::Command;
+
+use crate::amplify::{Artifact, ArtifactType, ScanKind, ToolOptions};
+use crate::amplifyignore::AmplifyIgnore;
+use crate::cache::{self, ToolCache};
+use crate::common::ToolLog;
+use crate::profile::RepoProfile;
+use crate::tools::process::{self, Limits, Stdout};
+use crate::tools::{RunContext, ToolActions};
+use assets::{Asset, Platform};
+use report::Report;
+use rules::RuleSources;
+
+const OPENGREP_VERSION: &str = "1.16.1";
+const OPENGREP_BINARY_NAME: &str = "opengrep";
+const OPENGREP_RULES_FILE: &str = "ruleset.json";
+const OPENGREP_RULES_RELEASES: &str =
+    "https://github.com/amplify-security/opengrep-rules/releases/download";
+/// Release of the ruleset that is used when none is pinned.
+const OPENGREP_RULES_LATEST: &str = "latest";
+// Names of the binary and ruleset in the tool cache.
+const CACHE_TOOL: &str = "opengrep";
+const CACHE_RULES: &str = "opengrep-rules";
+
+/// Tool versions in the cache that this runner still uses.
+pub const CACHE_ENTRIES: &[(&str, &str)] = &[
+    (CACHE_TOOL, OPENGREP_VERSION),
+    (CACHE_RULES, OPENGREP_RULES_LATEST),
+];
+
+/// Languages that Opengrep has rules for.
+const OPENGREP_LANGUAGES: &[LanguageType] = &[
+    LanguageType::Bash,
+    LanguageType::C,
+    LanguageType::Clojure,
+    LanguageType::Cpp,
+    LanguageType::CSharp,
+    LanguageType::Dart,
+    LanguageType::Dockerfile,
+    LanguageType::Elixir,
+    LanguageType::Go,
+    LanguageType::Hcl,
+    LanguageType::Html,
+    LanguageType::Java,
+    LanguageType::JavaScript,
+    LanguageType::Json,
+    LanguageType::Jsx,
+    LanguageType::Julia,
+    LanguageType::Kotlin,
+    LanguageType::Lisp,
+    LanguageType::Lua,
+    LanguageType::OCaml,
+    LanguageType::Php,
+    LanguageType::Python,
+    LanguageType::R,
+    LanguageType::Ruby,
+    LanguageType::Rust,
+    LanguageType::Scala,
+    LanguageType::Scheme,
+    LanguageType::Swift,
+    LanguageType::Tsx,
+    LanguageType::TypeScript,
+    LanguageType::Xml,
+    LanguageType::Yaml,
+];
+
+#[derive(Debug, Default)]
+pub struct Opengrep {
+    options: ToolOptions,
+    /// Digest of the installed ruleset, set during setup.
+    rules_digest: OnceLock<String>,
+    /// Where each finding's rule came from, set during setup.
+    rule_sources: OnceLock<RuleSources>,
+    /// Custom rules as `--config` values, set during setup.
+    custom_rules: OnceLock<Vec<String>>,
+}
+
+impl Opengrep {
+    pub fn new(options: ToolOptions) -> Self {
+        Opengrep {
+            options,
+            rules_digest: OnceLock::new(),
+            rule_sources: OnceLock::new(),
+            custom_rules: OnceLock::new(),
+        }
+    }
+
+    /// Fetch the ruleset into the work directory and record its digest.
+    ///
+    /// When the ruleset is pinned by version or digest, it must match the
+    /// expected digest or setup fails. Otherwise the latest release is used.
+    async fn install_rules(&self, ctx: &RunContext, log: &ToolLog) -> Result<()> {
+        let version = self
+            .options
+            .rules_version
+            .as_deref()
+            .unwrap_or(OPENGREP_RULES_LATEST);
+        let expected = match (&self.options.rules_sha256, &self.options.rules_version) {
+            (Some(digest), _) => Some(
+                const_hex::decode_to_array(digest.trim())
+                    .wrap_err("`rules_sha256` is not a hex-encoded SHA-256.")?,
+            ),
+            (None, Some(version)) => Some(fetch_published_digest(version, log).await?),
+            (None, None) => None,
+        };
+        let rules = match &expected {
+            Some(expected) => {
+                self.fetch_pinned_rules(version, expected, &ctx.cache, log)
+                    .await?
+            }
+            None => self.fetch_latest_rules(&ctx.cache, log).await?,
+        };
+        let digest = format!("sha256:{}", const_hex::encode(Sha256::digest(&rules)));
+        log.println(format!("Using Amplify ruleset {version} ({digest})."));
+        let _ = self.rules_digest.set(digest);
+
+        let rules_path = ctx.work_dir.file(OPENGREP_RULES_FILE);
+        std::fs::write(&rules_path, rules)
+            .wrap_err_with(|| format!("Failed to write {}.", rules_path.display()))
+    }
+
+    /// Fetch a ruleset that must match `expected`, from the cache if it's
+    /// there.
+    async fn fetch_pinned_rules(
+        &self,
+        version: &str,
+        expected: &[u8; 32],
+        cache: &ToolCache,
+        log: &ToolLog,
+    ) -> Result<Vec<u8>> {
+        if let Some(rules) = cache.get(CACHE_RULES, version, expected, "rules.json") {
+            log.println("Using cached Amplify ruleset.");
+            return Ok(rules);
+        }
+        let uri = rules_uri(version, "rules.json");
+        log.println(format!("Fetching Amplify ruleset from {uri}."));
+        let rules = reqwest::get(&uri)
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .wrap_err("Failed to fetch Amplify ruleset for Opengrep.")?
+            .bytes()
+            .await?;
+        verify_rules(&rules, expected)?;
+        if let Err(e) = cache.put(CACHE_RULES, version, expected, "rules.json", &rules) {
+            log.println(format!("::warning::Could not cache Amplify ruleset: {e}"));
+        }
+        Ok(rules.to_vec())
+    }
+
+    /// Fetch the latest ruleset, reusing the cached copy when the release
+    /// hasn't changed since it was downloaded.
+    async fn fetch_latest_rules(&self, cache: &ToolCache, log: &ToolLog) -> Result<Vec<u8>> {
+        let cache_dir = cache.version_dir(CACHE_RULES, OPENGREP_RULES_LATEST);
+        let cached_rules = cache_dir.join("rules.json");
+        let cached_etag = cache_dir.join("rules.etag");
+
+        let uri = rules_uri(OPENGREP_RULES_LATEST, "rules.json");
+        log.println(format!("Fetching Amplify ruleset from {uri}."));
+        let mut request = reqwest::Client::new().get(&uri);
+        if let (true, Ok(etag)) = (cached_rules.exists(), std::fs::read_to_string(&cached_etag)) {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag.trim());
+        }
+        let response = request
+            .send()
+            .await
+            .wrap_err("Failed to fetch Amplify ruleset for Opengrep.")?;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            log.println("Amplify ruleset is unchanged, using the cached copy.");
+            return std::fs::read(&cached_rules).wrap_err("Failed to read cached Amplify ruleset.");
+        }
+        let response = response
+            .error_for_status()
+            .wrap_err("Failed to fetch Amplify ruleset for Opengrep.")?;
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(str::to_owned);
+        let rules = response.bytes().await?.to_vec();
+        if let Some(etag) = etag {
+            // The ETag is written last so that it never describes a
+            // ruleset that failed to be cached.
+            let cached = cache::write_atomically(&cached_rules, &rules)
+                .and_then(|()| cache::write_atomically(&cached_etag, etag.as_bytes()));
+            if let Err(e) = cached {
+                log.println(format!("::warning::Could not cache Amplify ruleset: {e}"));
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Download the Opengrep binary for `asset`, or take it from the cache.
+    async fn fetch_binary(
+        &self,
+        asset: &Asset,
+        checksum: &[u8; 32],
+        cache: &ToolCache,
+        log: &ToolLog,
+    ) -> Result<Vec<u8>> {
+        if let Some(binary) = cache.get(CACHE_TOOL, OPENGREP_VERSION, checksum, asset.name) {
+            log.println(format!(
+                "Using cached Opengrep binary from {}.",
+                cache.root().display()
+            ));
+            return Ok(binary);
+        }
+        let binary_url = format!(
+            "https://github.com/{repository}/releases/download/v{version}/{binary_name}",
+            repository = "opengrep/opengrep",
+            version = OPENGREP_VERSION,
+            binary_name = asset.name
+        );
+        log.println(format!("Fetching Opengrep binary from {binary_url}."));
+        let opengrep_binary = reqwest::get(binary_url)
+            .await
+            .wrap_err("Failed to fetch Opengrep binary.")?
+            .bytes()
+            .await?;
+        log.println("Verifying Opengrep binary checksum.");
+        let hash = Sha256::digest(&opengrep_binary);
+        if hash[..] != checksum[..] {
+            return Err(eyre!(
+                "Opengrep binary failed checksum verification. Expected {}, got {}.",
+                const_hex::display(checksum),
+                const_hex::display(&hash)
+            ));
+        }
+        if let Err(e) = cache.put(
+            CACHE_TOOL,
+            OPENGREP_VERSION,
+            checksum,
+            asset.name,
+            &opengrep_binary,
+        ) {
+            log.println(format!("::warning::Could not cache Opengrep binary: {e}"));
+        }
+        Ok(opengrep_binary.to_vec())
+    }
+}
+
+impl ToolActions for Opengrep {
+    fn name(&self) -> &'static str {
+        "opengrep"
+    }
+
+    fn skip_reason(&self, profile: &RepoProfile) -> Option<String> {
+        if profile.has_any_language(OPENGREP_LANGUAGES) {
+            None
+        } else {
+            Some("no files in a language that Opengrep supports were found.".to_owned())
+        }
+    }
+
+    async fn setup(&self, ctx: &RunContext, log: &ToolLog) -> Result<()> {
+        let platform = Platform::detect()?;
+        let asset = Asset::for_platform(platform)?;
+        let checksum = asset.expected_checksum(log)?;
+        log.println(format!("Detected platform {platform}."));
+        let binary_path = ctx.work_dir.binary(OPENGREP_BINARY_NAME);
+        if is_installed(&binary_path, &checksum) {
+            log.println(format!(
+                "Opengrep {OPENGREP_VERSION} is already installed at {}.",
+                binary_path.display()
+            ));
+        } else {
+            let opengrep_binary = self.fetch_binary(asset, &checksum, &ctx.cache, log).await?;
+            log.println(format!("Creating {}.", binary_path.display()));
+            ctx.work_dir
+                .install_binary(&binary_path, &opengrep_binary)?;
+        }
+        // The ruleset is the same for every project, so it's fetched once
+        // with the binary rather than before each scan.
+        self.install_rules(ctx, log).await?;
+        let custom_rules = rules::resolve(&self.options.rules, &ctx.work_dir, log).await?;
+        rules::validate(&binary_path, &custom_rules, log).await?;
+        if !custom_rules.is_empty() {
+            log.println(format!("Using custom rules: {}.", custom_rules.join(", ")));
+        }
+        let _ = self.rule_sources.set(RuleSources::new(
+            &ctx.work_dir.file(OPENGREP_RULES_FILE),
+            &custom_rules,
+        ));
+        let _ = self.custom_rules.set(custom_rules);
+
+        log.println("Completed opengrep installation.");
+        Ok(())
+    }
+
+    async fn launch(&self, ctx: &RunContext, log: &ToolLog) -> Result<Artifact> {
+        let search_paths: String = env::var("PATH").expect("Couldn't identify PATH.");
+        // `opengrep ci` only scans the whole repository in the working
+        // directory, so specific paths are scanned with `opengrep scan`.
+        let scan_whole_repo = ctx.paths.iter().all(|path| path == ".");
+        let mut opengrep_scan = Command::new(ctx.work_dir.binary(OPENGREP_BINARY_NAME));
+        opengrep_scan
+            .arg(if scan_whole_repo { "ci" } else { "scan" })
+            // When public-api supports SARIF artifact ingestion, just change --json to --sarif here and update the return type
+            .args(["--json", "--oss-only", "--taint-intrafile"])
+            // Rules are passed with --config rather than SEMGREP_RULES, since
+            // any --config flag overrides the environment variable.
+            .arg("--config")
+            .arg(ctx.work_dir.file(OPENGREP_RULES_FILE))
+            .args(
+                self.custom_rules
+                    .get()
+                    .unwrap_or(&self.options.rules)
+                    .iter()
+                    .flat_map(|rule| ["--config", rule]),
+            )
+            .args(
+                self.options
+                    .exclude
+                    .iter()
+                    .flat_map(|pattern| ["--exclude", pattern]),
+            )
+            .args(
+                ctx.ignore
+                    .excludes()
+                    .iter()
+                    .flat_map(|pattern| ["--exclude", pattern]),
+            )
+            .args(
+                ctx.baseline_commit
+                    .iter()
+                    .flat_map(|commit| ["--baseline-commit", commit]),
+            )
+            .args(&self.options.extra_args)
+            .args(if scan_whole_repo {
+                &[][..]
+            } else {
+                &ctx.paths[..]
+            })
+            .env("PATH", format!("{search_paths}:/opengrep/bin"))
+            .env("SEMGREP_IN_DOCKER", "1")
+            .env("SEMGREP_USER_AGENT_APPEND", "Docker");
+        log.println(format!("Starting Opengrep scan: {opengrep_scan:?}"));
+
+        let limits = Limits::new(&self.options, ctx);
+        let spill_path = ctx
+            .work_dir
+            .file(&format!("opengrep-{}.json", report_id(&ctx.paths)));
+        let result = process::run(
+            &mut opengrep_scan,
+            &ctx.tool_label("Opengrep"),
+            limits,
+            Some(spill_path),
+        )
+        .await?;
+        log.println("Finished Opengrep scan.");
+
+        let mut success = false;
+        match result.status.code() {
+            Some(code) => {
+                // Per Semgrep documentation (https://semgrep.dev/docs/cli-reference#exit-codes):
+                //   Semgrep can finish with the following exit codes:
+                //     0: Semgrep ran successfully and found no errors (or did find errors, but the --error flag is not
+                //        being used).
+                //     1: Semgrep ran successfully and found issues in your code (while using the --error flag).
+                // so we can treat 1 as success as well since we get a scan result.
+                if code == 0 || code == 1 {
+                    success = true;
+                } else {
+                    log.println(format!("Exited with non-successful exit code: {code}"));
+                }
+            }
+            None => match result.status.signal() {
+                Some(signal) if signal == libc::SIGXCPU || signal == libc::SIGKILL => {
+                    log.println(format!(
+                        "Scan was terminated by signal {signal}, possibly for exceeding its memory or CPU limit."
+                    ))
+                }
+                _ => log.println("Scan was prematurely terminated by an external signal."),
+            },
+        }
+        if !success {
+            return Err(eyre!("Opengrep scan did not complete successfully."));
+        }
+        let (contents, findings) =
+            process_report(result.stdout, &ctx.ignore, self.rule_sources.get(), log)?;
+        Ok(Artifact {
+            artifact_type: ArtifactType::Json,
+            contents,
+            rules_digest: self.rules_digest.get().cloned(),
+            scan_kind: if ctx.baseline_commit.is_some() {
+                ScanKind::Diff
+            } else {
+                ScanKind::Full
+            },
+            findings,
+        })
+    }
+}
+
+/// A name for the report of a scan of `paths` that's unique within the run,
+/// since monorepo projects are scanned at the same time.
+fn report_id(paths: &[String]) -> String {
+    const_hex::encode(&Sha256::digest(paths.join("\n"))[..8])
+}
+
+fn rules_uri(version: &str, file: &str) -> String {
+    format!("{OPENGREP_RULES_RELEASES}/{version}/{file}")
+}
+
+/// Fetch the SHA-256 published as `rules.json.sha256` with a ruleset
+/// release.
+async fn fetch_published_digest(version: &str, log: &ToolLog) -> Result<[u8; 32]> {
+    let uri = rules_uri(version, "rules.json.sha256");
+    log.println(format!("Fetching Amplify ruleset digest from {uri}."));
+    let published = reqwest::get(&uri)
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .wrap_err_with(|| format!("Failed to fetch the digest for Amplify ruleset {version}."))?
+        .text()
+        .await?;
+    parse_published_digest(&published)
+        .ok_or_else(|| eyre!("The published digest for Amplify ruleset {version} is malformed."))
+}
+
+/// Parse a digest in `sha256sum` format, i.e. the hex digest optionally
+/// followed by a file name.
+fn parse_published_digest(published: &str) -> Option<[u8; 32]> {
+    let digest = published.split_whitespace().next()?;
+    const_hex::decode_to_array(digest).ok()
+}
+
+fn verify_rules(rules: &[u8], expected: &[u8; 32]) -> Result<()> {
+    let digest = Sha256::digest(rules);
+    if digest[..] != expected[..] {
+        return Err(eyre!(
+            "Amplify ruleset failed checksum verification. Expected {}, got {}.",
+            const_hex::display(expected),
+            const_hex::display(&digest)
+        ));
+    }
+    Ok(())
+}
+
+/// Whether the binary at `path` is the release with `checksum`.
+fn is_installed(path: &Path, checksum: &[u8; 32]) -> bool {
+    std::fs::read(path).is_ok_and(|binary| Sha256::digest(binary)[..] == checksum[..])
+}
+
+/// Drop findings for ignored paths from an Opengrep JSON report, tag the
+/// rest with the source of their rule, and summarize what was found. The
+/// report is read from where the scan left it, which may be a file, and the
+/// processed report is written back there.
+fn process_report(
+    report: Stdout,
+    ignore: &AmplifyIgnore,
+    rule_sources: Option<&RuleSources>,
+    log: &ToolLog,
+) -> Result<(Stdout, Option<Report>)> {
+    let parsed = Report::from_reader(report.reader()?);
+    if ignore.is_empty() && rule_sources.is_none() {
+        return Ok((report, summarize(parsed, log)));
+    }
+    let mut findings = parsed.wrap_err("Failed to parse Opengrep JSON output.")?;
+    drop_ignored_results(&mut findings, ignore, log);
+    if let Some(rule_sources) = rule_sources {
+        rules::tag_rule_sources(&mut findings, rule_sources);
+    }
+    findings.summarize(log);
+    Ok((report.write_json(&findings)?, Some(findings)))
+}
+
+fn summarize(report: serde_json::Result<Report>, log: &ToolLog) -> Option<Report> {
+    match report {
+        Ok(report) => {
+            report.summarize(log);
+            Some(report)
+        }
+        Err(e) => {
+            log.println(format!(
+                "::warning::Could not summarize the Opengrep report: {e}"
+            ));
+            None
+        }
+    }
+}
+
+/// Remove results and scanned paths that `.amplifyignore` excludes from an
+/// Opengrep report, in case the scan still included them.
+fn drop_ignored_results(report: &mut Report, ignore: &AmplifyIgnore, log: &ToolLog) {
+    let total = report.results.len();
+    report
+        .results
+        .retain(|finding| !ignore.is_ignored(&finding.path));
+    if report.results.len() < total {
+        log.println(format!(
+            "Dropped {} result(s) for paths in .amplifyignore.",
+            total - report.results.len()
+        ));
+    }
+    report.paths.scanned.retain(|path| !ignore.is_ignored(path));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_support::TempDir;
+    use crate::workdir::WorkDir;
+
+    #[test]
+    fn test_drop_ignored_results() {
+        let ignore = AmplifyIgnore::parse("vendor/\n").unwrap();
+        let mut report = Report::parse(
+            r#"{"results":[{"path":"vendor/a.js"},{"path":"src/b.js"}],"errors":[],
+                "paths":{"scanned":["vendor/a.js","src/b.js"]}}"#,
+        )
+        .unwrap();
+        let log = ToolLog::default();
+
+        drop_ignored_results(&mut report, &ignore, &log);
+
+        assert_eq!(report.results.len(), 1);
+        assert_eq!(report.results[0].path, "src/b.js");
+        assert_eq!(report.paths.scanned, ["src/b.js"]);
+        assert_eq!(
+            log.lines(),
+            ["Dropped 1 result(s) for paths in .amplifyignore."]
+        );
+    }
+
+    #[test]
+    fn test_process_report_is_a_no_op_without_ignores_or_rules() {
+        let report = Stdout::Memory(b"not json".to_vec());
+        let log = ToolLog::default();
+        let (processed, findings) =
+            process_report(report.clone(), &AmplifyIgnore::default(), None, &log).unwrap();
+
+        assert_eq!(processed, report);
+        assert_eq!(findings, None);
+        assert!(log.lines()[0].starts_with("::warning::Could not summarize"));
+    }
+
+    #[test]
+    fn test_process_report_tags_rule_sources() {
+        let report = r#"{"results":[{"check_id":"no-eval","path":"a.py"}]}"#;
+        let rule_sources = RuleSources::new(Path::new("ruleset.json"), &[]);
+
+        let (processed, findings) = process_report(
+            Stdout::Memory(report.as_bytes().to_vec()),
+            &AmplifyIgnore::default(),
+            Some(&rule_sources),
+            &ToolLog::default(),
+        )
+        .unwrap();
+        let processed: serde_json::Value =
+            serde_json::from_str(&processed.read_to_string().unwrap()).unwrap();
+
+        assert_eq!(findings.unwrap().results[0].check_id, "no-eval");
+        assert_eq!(
+            processed["results"][0]["extra"]["metadata"]["rule_source"],
+            "amplify"
+        );
+    }
+
+    #[test]
+    fn test_rule_ids_are_the_same_in_every_work_dir() {
+        let rule_ids: Vec<String> = (0..2)
+            .map(|_| {
+                let root = TempDir::new("work");
+                let work_dir = WorkDir::create(Some(root.to_path_buf())).unwrap();
+                let ruleset = work_dir.file(OPENGREP_RULES_FILE);
+                // Opengrep prefixes rule IDs with the ruleset's directory.
+                let prefix = ruleset
+                    .parent()
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('/', ".");
+                let report = format!(
+                    r#"{{"results":[{{"check_id":"{}.no-eval","path":"a.py"}}]}}"#,
+                    prefix.trim_start_matches('.')
+                );
+
+                let (_, findings) = process_report(
+                    Stdout::Memory(report.into_bytes()),
+                    &AmplifyIgnore::default(),
+                    Some(&RuleSources::new(&ruleset, &[])),
+                    &ToolLog::default(),
+                )
+                .unwrap();
+                findings.unwrap().results[0].check_id.clone()
+            })
+            .collect();
+
+        assert_eq!(rule_ids, ["no-eval", "no-eval"]);
+    }
+
+    #[test]
+    fn test_parse_published_digest() {
+        let digest = "ab".repeat(32);
+        assert_eq!(parse_published_digest(&digest), Some([0xab; 32]));
+        assert_eq!(
+            parse_published_digest(&format!("{digest}  rules.json\n")),
+            Some([0xab; 32])
+        );
+        assert_eq!(parse_published_digest("not a digest"), None);
+        assert_eq!(parse_published_digest(""), None);
+    }
+
+    #[test]
+    fn test_verify_rules_fails_closed() {
+        let expected: [u8; 32] = Sha256::digest(b"{}").into();
+        assert!(verify_rules(b"{}", &expected).is_ok());
+        assert!(verify_rules(b"{\"rules\":[]}", &expected).is_err());
+    }
+
+    #[test]
+    fn test_is_installed_checks_the_binary() {
+        let dir = TempDir::new("opengrep");
+        let path = dir.join("opengrep");
+        let checksum: [u8; 32] = Sha256::digest(b"opengrep").into();
+        assert!(!is_installed(&path, &checksum));
+
+        std::fs::write(&path, "opengrep").unwrap();
+        assert!(is_installed(&path, &checksum));
+        assert!(!is_installed(&path, &[0; 32]));
+    }
+
+    #[test]
+    fn test_opengrep_only_applies_to_supported_languages() {
+        let opengrep = Opengrep::default();
+        let mut profile = RepoProfile::default();
+        assert!(opengrep.skip_reason(&profile).is_some());
+
+        profile.languages.insert(LanguageType::Python);
+        assert!(opengrep.skip_reason(&profile).is_none());
+    }
+}
@@ -0,0 +1,485 @@
+//! Typed model of Opengrep's JSON output, which is the same as Semgrep's.
+//!
+//! The report is parsed once, filtered and tagged in this form, and written
+//! back out for Amplify. Fields the model doesn't name are kept as they are,
+//! so the uploaded report has everything Opengrep wrote. The model is also
+//! used to tell the user what was found: a table of findings, grouped by
+//! severity, rule and file, and a warning for every error the scan ran into,
+//! such as a file that couldn't be parsed or a rule that timed out.
+
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::common::ToolLog;
+use crate::github;
+
+/// Most rows to print in the findings table.
+const MAX_TABLE_ROWS: usize = 50;
+
+/// Fields of a report object that the model doesn't name.
+type OtherFields = serde_json::Map<String, serde_json::Value>;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Report {
+    /// Version of Opengrep that produced the report.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    pub results: Vec<Finding>,
+    pub errors: Vec<ScanError>,
+    pub paths: ScannedPaths,
+    #[serde(flatten)]
+    pub other: OtherFields,
+}
+
+// Deserialized by hand because `#[serde(flatten)]` would buffer the whole
+// report before parsing it, and a report can be up to the spill limit.
+impl<'de> Deserialize<'de> for Report {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ReportVisitor;
+
+        impl<'de> Visitor<'de> for ReportVisitor {
+            type Value = Report;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an Opengrep JSON report")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Report, A::Error> {
+                let mut report = Report::default();
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "version" => report.version = map.next_value()?,
+                        "results" => report.results = map.next_value()?,
+                        "errors" => report.errors = map.next_value()?,
+                        "paths" => report.paths = map.next_value()?,
+                        _ => {
+                            report.other.insert(key, map.next_value()?);
+                        }
+                    }
+                }
+                Ok(report)
+            }
+        }
+
+        deserializer.deserialize_map(ReportVisitor)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Finding {
+    pub check_id: String,
+    pub path: String,
+    pub start: Position,
+    pub end: Position,
+    pub extra: FindingExtra,
+    #[serde(flatten)]
+    pub other: OtherFields,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Position {
+    pub line: u64,
+    pub col: u64,
+    #[serde(flatten)]
+    pub other: OtherFields,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(from = "RawFindingExtra", into = "RawFindingExtra")]
+pub struct FindingExtra {
+    pub message: String,
+    pub severity: Severity,
+    /// Rule metadata, such as CWE and OWASP references, as the rule sets it.
+    pub metadata: serde_json::Value,
+    /// The severity as the rule spells it, e.g. `ERROR`, which is what the
+    /// uploaded report keeps.
+    level: Option<String>,
+    other: OtherFields,
+}
+
+/// [`FindingExtra`] as it appears in the report.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+struct RawFindingExtra {
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    severity: Option<String>,
+    #[serde(skip_serializing_if = "serde_json::Value::is_null")]
+    metadata: serde_json::Value,
+    #[serde(flatten)]
+    other: OtherFields,
+}
+
+impl From<RawFindingExtra> for FindingExtra {
+    fn from(raw: RawFindingExtra) -> Self {
+        FindingExtra {
+            message: raw.message,
+            severity: raw
+                .severity
+                .as_deref()
+                .and_then(|level| level.parse().ok())
+                .unwrap_or_default(),
+            metadata: raw.metadata,
+            level: raw.severity,
+            other: raw.other,
+        }
+    }
+}
+
+impl From<FindingExtra> for RawFindingExtra {
+    fn from(extra: FindingExtra) -> Self {
+        RawFindingExtra {
+            message: extra.message,
+            severity: extra.level,
+            metadata: extra.metadata,
+            other: extra.other,
+        }
+    }
+}
+
+/// Severity of a finding. Rules may use Semgrep's original levels or the
+/// newer ones, which are treated the same: `INFO` is `LOW`, `WARNING` is
+/// `MEDIUM` and `ERROR` is `HIGH`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+    /// Levels that don't describe a vulnerability, e.g. `INVENTORY`.
+    #[default]
+    Unknown,
+}
+
+impl Ord for Severity {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+impl PartialOrd for Severity {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Severity {
+    /// Position from least to most severe, with unknown levels below all
+    /// others.
+    fn rank(self) -> u8 {
+        match self {
+            Severity::Unknown => 0,
+            Severity::Low => 1,
+            Severity::Medium => 2,
+            Severity::High => 3,
+            Severity::Critical => 4,
+        }
+    }
+
+    /// Every severity, from most to least severe.
+    pub const ALL: [Severity; 5] = [
+        Severity::Critical,
+        Severity::High,
+        Severity::Medium,
+        Severity::Low,
+        Severity::Unknown,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Unknown => "unknown",
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "low" | "info" => Ok(Severity::Low),
+            "medium" | "warning" => Ok(Severity::Medium),
+            "high" | "error" => Ok(Severity::High),
+            "critical" => Ok(Severity::Critical),
+            _ => Err(format!(
+                "Unknown severity `{s}`, expected low, medium, high or critical."
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Something that went wrong during the scan without failing it.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ScanError {
+    /// `error` or `warn`.
+    pub level: String,
+    /// Kind of error, e.g. `Syntax error` or `Timeout`. Some kinds are
+    /// reported as a list with the name first.
+    #[serde(rename = "type", skip_serializing_if = "serde_json::Value::is_null")]
+    pub kind: serde_json::Value,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(flatten)]
+    pub other: OtherFields,
+}
+
+impl ScanError {
+    pub fn kind(&self) -> &str {
+        match &self.kind {
+            serde_json::Value::String(kind) => kind,
+            serde_json::Value::Array(parts) => parts
+                .first()
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or("Error"),
+            _ => "Error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ScannedPaths {
+    pub scanned: Vec<String>,
+    pub skipped: Vec<SkippedPath>,
+    #[serde(flatten)]
+    pub other: OtherFields,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SkippedPath {
+    pub path: String,
+    pub reason: String,
+    #[serde(flatten)]
+    pub other: OtherFields,
+}
+
+impl Report {
+    #[cfg(test)]
+    pub fn parse(report: &str) -> serde_json::Result<Report> {
+        serde_json::from_str(report)
+    }
+
+    pub fn from_reader(report: impl std::io::Read) -> serde_json::Result<Report> {
+        serde_json::from_reader(report)
+    }
+
+    /// Number of findings of each severity.
+    pub fn severity_counts(&self) -> BTreeMap<Severity, usize> {
+        let mut counts = BTreeMap::new();
+        for finding in &self.results {
+            *counts.entry(finding.extra.severity).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Findings grouped by severity, rule and file, most severe first.
+    fn table(&self) -> Vec<(Severity, &str, &str, usize)> {
+        let mut groups: BTreeMap<(std::cmp::Reverse<Severity>, &str, &str), usize> =
+            BTreeMap::new();
+        for finding in &self.results {
+            let key = (
+                std::cmp::Reverse(finding.extra.severity),
+                finding.check_id.as_str(),
+                finding.path.as_str(),
+            );
+            *groups.entry(key).or_default() += 1;
+        }
+        groups
+            .into_iter()
+            .map(|((severity, rule, path), count)| (severity.0, rule, path, count))
+            .collect()
+    }
+
+    /// Write what the scan found to `log`: totals, a table of findings, and
+    /// a warning for each scan error.
+    pub fn summarize(&self, log: &ToolLog) {
+        log.println(format!(
+            "Opengrep {} scanned {} file(s) and skipped {}.",
+            self.version.as_deref().unwrap_or("(unknown version)"),
+            self.paths.scanned.len(),
+            self.paths.skipped.len()
+        ));
+        let counts = self.severity_counts();
+        let totals: Vec<String> = Severity::ALL
+            .into_iter()
+            .filter_map(|severity| {
+                counts
+                    .get(&severity)
+                    .map(|count| format!("{count} {severity}"))
+            })
+            .collect();
+        if totals.is_empty() {
+            log.println("Found no findings.");
+        } else {
+            log.println(format!(
+                "Found {} finding(s): {}.",
+                self.results.len(),
+                totals.join(", ")
+            ));
+            for line in format_table(&self.table()) {
+                log.println(line);
+            }
+        }
+        for error in &self.errors {
+            let location = error
+                .path
+                .as_deref()
+                .map(|path| format!(" in {path}"))
+                .unwrap_or_default();
+            let message = format!("Opengrep {}{location}: {}", error.kind(), error.message);
+            log.println(format!("::warning::{}", github::escape_data(&message)));
+        }
+    }
+}
+
+fn format_table(rows: &[(Severity, &str, &str, usize)]) -> Vec<String> {
+    const HEADERS: [&str; 4] = ["Severity", "Rule", "File", "Count"];
+    let shown = &rows[..rows.len().min(MAX_TABLE_ROWS)];
+    let rule_width = shown
+        .iter()
+        .map(|row| row.1.len())
+        .chain([HEADERS[1].len()])
+        .max()
+        .unwrap_or_default();
+    let file_width = shown
+        .iter()
+        .map(|row| row.2.len())
+        .chain([HEADERS[2].len()])
+        .max()
+        .unwrap_or_default();
+    let mut lines = vec![format!(
+        "{:<8}  {:<rule_width$}  {:<file_width$}  {}",
+        HEADERS[0], HEADERS[1], HEADERS[2], HEADERS[3]
+    )];
+    for (severity, rule, file, count) in shown {
+        lines.push(format!(
+            "{:<8}  {rule:<rule_width$}  {file:<file_width$}  {count}",
+            severity.as_str()
+        ));
+    }
+    if rows.len() > shown.len() {
+        lines.push(format!("... and {} more.", rows.len() - shown.len()));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = r#"{
+        "version": "1.16.1",
+        "results": [
+            {"check_id": "no-eval", "path": "src/a.py", "start": {"line": 3, "col": 1}, "end": {"line": 3, "col": 9},
+             "extra": {"message": "eval", "severity": "ERROR", "metadata": {"cwe": ["CWE-95"]}}},
+            {"check_id": "no-eval", "path": "src/a.py", "start": {"line": 7, "col": 1}, "end": {"line": 7, "col": 9},
+             "extra": {"message": "eval", "severity": "ERROR"}},
+            {"check_id": "weak-hash", "path": "src/b.py", "start": {"line": 1, "col": 1}, "end": {"line": 1, "col": 5},
+             "extra": {"message": "md5", "severity": "LOW"}},
+            {"check_id": "inventory", "path": "src/b.py", "extra": {"severity": "INVENTORY"}}
+        ],
+        "errors": [
+            {"code": 3, "level": "warn", "type": ["PartialParsing", [{"path": "src/c.js"}]], "message": "Syntax error at line 2\nmore 100%", "path": "src/c.js"},
+            {"code": 2, "level": "error", "type": "Timeout", "message": "Timed out"}
+        ],
+        "paths": {"scanned": ["src/a.py", "src/b.py", "src/c.js"], "skipped": [{"path": "big.min.js", "reason": "exceeded_size_limit"}]}
+    }"#;
+
+    #[test]
+    fn test_parse_report() {
+        let report = Report::parse(REPORT).unwrap();
+
+        assert_eq!(report.version.as_deref(), Some("1.16.1"));
+        assert_eq!(report.results[0].start.line, 3);
+        assert_eq!(report.results[0].start.col, 1);
+        assert_eq!(report.results[0].extra.severity, Severity::High);
+        assert_eq!(report.results[3].extra.severity, Severity::Unknown);
+        assert_eq!(report.errors[0].kind(), "PartialParsing");
+        assert_eq!(report.errors[1].kind(), "Timeout");
+        assert_eq!(report.paths.skipped[0].reason, "exceeded_size_limit");
+        assert_eq!(
+            report.severity_counts(),
+            BTreeMap::from([
+                (Severity::Unknown, 1),
+                (Severity::Low, 1),
+                (Severity::High, 2)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_report_round_trips() {
+        let report = Report::parse(REPORT).unwrap();
+        let written = serde_json::to_value(&report).unwrap();
+
+        let mut expected: serde_json::Value = serde_json::from_str(REPORT).unwrap();
+        // Opengrep always writes these, and the test report leaves them out.
+        expected["results"][3]["extra"]["message"] = "".into();
+        expected["results"][3]["start"] = serde_json::json!({"line": 0, "col": 0});
+        expected["results"][3]["end"] = serde_json::json!({"line": 0, "col": 0});
+        assert_eq!(written, expected);
+        assert_eq!(written["results"][0]["extra"]["severity"], "ERROR");
+    }
+
+    #[test]
+    fn test_severity_from_str() {
+        assert_eq!("high".parse(), Ok(Severity::High));
+        assert_eq!("WARNING".parse(), Ok(Severity::Medium));
+        assert!("unknown".parse::<Severity>().is_err());
+        assert!(Severity::Critical > Severity::High);
+        assert!(Severity::Unknown < Severity::Low);
+    }
+
+    #[test]
+    fn test_summarize() {
+        let log = ToolLog::default();
+        Report::parse(REPORT).unwrap().summarize(&log);
+
+        assert_eq!(
+            log.lines(),
+            [
+                "Opengrep 1.16.1 scanned 3 file(s) and skipped 1.",
+                "Found 4 finding(s): 2 high, 1 low, 1 unknown.",
+                "Severity  Rule       File      Count",
+                "high      no-eval    src/a.py  2",
+                "low       weak-hash  src/b.py  1",
+                "unknown   inventory  src/b.py  1",
+                "::warning::Opengrep PartialParsing in src/c.js: Syntax error at line 2%0Amore 100%25",
+                "::warning::Opengrep Timeout: Timed out",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_summarize_empty_report() {
+        let log = ToolLog::default();
+        Report::parse(r#"{"results":[],"errors":[]}"#)
+            .unwrap()
+            .summarize(&log);
+        assert_eq!(
+            log.lines(),
+            [
+                "Opengrep (unknown version) scanned 0 file(s) and skipped 0.",
+                "Found no findings."
+            ]
+        );
+    }
+}
@@ -0,0 +1,307 @@
+//! Custom rules that run alongside the Amplify ruleset.
+//!
+//! Rule files in `.amplify/rules/` are picked up automatically, and the
+//! tool's `rules` option can add more paths or URLs. URLs are downloaded
+//! into the work directory once, during setup, and every custom rule file is
+//! validated before any scan starts.
+//!
+//! Findings are tagged with where their rule came from in
+//! `extra.metadata.rule_source`: `amplify` for the Amplify ruleset and
+//! `custom` for everything else. Opengrep prefixes the ID of every rule
+//! loaded from a file with that file's directory, so the source is told
+//! apart by that prefix rather than by the rule's own ID, which a custom
+//! rule could share. The prefix is then removed from `check_id`: the
+//! ruleset is in the run's work directory, whose name changes every run,
+//! and rule IDs must stay the same across runs for findings to be tracked.
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+use super::report::Report;
+use crate::common::ToolLog;
+use crate::workdir::WorkDir;
+
+/// Directory of custom rule files, relative to the working directory.
+pub const CUSTOM_RULES_DIR: &str = ".amplify/rules";
+
+const RULE_FILE_EXTENSIONS: &[&str] = &["json", "yaml", "yml"];
+
+pub const RULE_SOURCE_AMPLIFY: &str = "amplify";
+pub const RULE_SOURCE_CUSTOM: &str = "custom";
+
+/// Rule files in `dir`, sorted by name so that runs are reproducible.
+pub fn rule_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .wrap_err_with(|| format!("Failed to read {}.", dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .and_then(|extension| extension.to_str())
+                    .is_some_and(|extension| RULE_FILE_EXTENSIONS.contains(&extension))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn is_url(rule: &str) -> bool {
+    rule.starts_with("https://") || rule.starts_with("http://")
+}
+
+/// Turn the configured rules into `--config` values for Opengrep, with the
+/// files in `.amplify/rules/` first and URLs replaced by downloaded copies.
+pub async fn resolve(rules: &[String], work_dir: &WorkDir, log: &ToolLog) -> Result<Vec<String>> {
+    let mut resolved: Vec<String> = rule_files(Path::new(CUSTOM_RULES_DIR))?
+        .into_iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    for (index, rule) in rules.iter().enumerate() {
+        if !is_url(rule) {
+            resolved.push(rule.clone());
+            continue;
+        }
+        log.println(format!("Fetching custom rules from {rule}."));
+        let body = reqwest::get(rule)
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .wrap_err_with(|| format!("Failed to fetch custom rules from {rule}."))?
+            .bytes()
+            .await?;
+        // Opengrep picks the parser from the extension, so it's kept.
+        let extension = Path::new(rule)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .filter(|extension| RULE_FILE_EXTENSIONS.contains(extension))
+            .unwrap_or("yaml");
+        let path = work_dir.file(&format!("custom-rules/{index}.{extension}"));
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .wrap_err_with(|| format!("Failed to create {}.", dir.display()))?;
+        }
+        std::fs::write(&path, body)
+            .wrap_err_with(|| format!("Failed to write {}.", path.display()))?;
+        resolved.push(path.to_string_lossy().into_owned());
+    }
+    Ok(resolved)
+}
+
+/// Check that every local custom rule file is valid, so that a broken rule
+/// fails setup instead of every scan.
+pub async fn validate(binary: &Path, rules: &[String], log: &ToolLog) -> Result<()> {
+    // Registry packs and other non-file configs are checked by Opengrep
+    // when the scan starts.
+    let files: Vec<&String> = rules
+        .iter()
+        .filter(|rule| Path::new(rule).is_file())
+        .collect();
+    if files.is_empty() {
+        return Ok(());
+    }
+    log.println(format!("Validating {} custom rule file(s).", files.len()));
+    let output = Command::new(binary)
+        .args(["scan", "--validate"])
+        .args(files.iter().flat_map(|rule| ["--config", rule.as_str()]))
+        .kill_on_drop(true)
+        .output()
+        .await
+        .wrap_err("Failed to run Opengrep to validate custom rules.")?;
+    if !output.status.success() {
+        log.println(String::from_utf8_lossy(&output.stderr).trim_end());
+        return Err(eyre!(
+            "Custom rules failed validation: {}",
+            files
+                .iter()
+                .map(|rule| rule.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// The prefix Opengrep gives the IDs of rules loaded from the file at
+/// `path`: the file's directory with `/` replaced by `.` and without leading
+/// dots, e.g. `amplify.rules` for `.amplify/rules/internal.yml`.
+fn rule_id_prefix(path: &Path) -> String {
+    let dir = path
+        .parent()
+        .map(|dir| dir.to_string_lossy())
+        .unwrap_or_default();
+    dir.replace('/', ".").trim_start_matches('.').to_owned()
+}
+
+/// The `check_id` prefixes of the Amplify ruleset and of the custom rules,
+/// which tell which of them a finding's rule was loaded from.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RuleSources {
+    amplify: String,
+    custom: Vec<String>,
+}
+
+impl RuleSources {
+    /// Sources for a scan with the Amplify ruleset at `ruleset` and the
+    /// `--config` values in `custom_rules`. Registry packs aren't files, so
+    /// their rules have no prefix to tell them by.
+    pub fn new(ruleset: &Path, custom_rules: &[String]) -> Self {
+        let custom = custom_rules
+            .iter()
+            .map(Path::new)
+            .filter_map(|rule| {
+                if rule.is_dir() {
+                    // Files in a directory get that directory as a prefix,
+                    // and those in subdirectories a longer one.
+                    Some(rule_id_prefix(&rule.join("rules.yml")))
+                } else if rule.is_file() {
+                    Some(rule_id_prefix(rule))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        RuleSources {
+            amplify: rule_id_prefix(ruleset),
+            custom,
+        }
+    }
+
+    /// Where the rule behind an Opengrep `check_id` came from, and the
+    /// rule's own ID without the prefix. When both the ruleset's and a
+    /// custom file's prefix match, the longer one wins.
+    fn source<'a>(&self, check_id: &'a str) -> (&'static str, &'a str) {
+        let strip = |prefix: &str| {
+            if prefix.is_empty() {
+                Some(check_id)
+            } else {
+                check_id.strip_prefix(prefix)?.strip_prefix('.')
+            }
+        };
+        let custom = self
+            .custom
+            .iter()
+            .filter_map(|prefix| Some((prefix.len(), strip(prefix)?)))
+            .max_by_key(|(len, _)| *len);
+        match (strip(&self.amplify), custom) {
+            (Some(rule_id), custom) if custom.is_none_or(|(len, _)| len < self.amplify.len()) => {
+                (RULE_SOURCE_AMPLIFY, rule_id)
+            }
+            (_, Some((_, rule_id))) => (RULE_SOURCE_CUSTOM, rule_id),
+            _ => (RULE_SOURCE_CUSTOM, check_id),
+        }
+    }
+}
+
+/// Add `extra.metadata.rule_source` to every result in an Opengrep report,
+/// and remove the prefix of the file its rule was loaded from.
+pub fn tag_rule_sources(report: &mut Report, sources: &RuleSources) {
+    for finding in &mut report.results {
+        let (source, rule_id) = sources.source(&finding.check_id);
+        finding.check_id = rule_id.to_owned();
+        let metadata = &mut finding.extra.metadata;
+        if metadata.is_null() {
+            *metadata = serde_json::json!({});
+        }
+        if let Some(metadata) = metadata.as_object_mut() {
+            metadata.insert("rule_source".to_owned(), source.into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_support::TempDir;
+
+    #[test]
+    fn test_rule_files_are_sorted_and_filtered() {
+        let dir = TempDir::new("rules");
+        for file in ["b.yml", "a.yaml", "c.json", "README.md"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+
+        assert_eq!(
+            rule_files(&dir).unwrap(),
+            vec![dir.join("a.yaml"), dir.join("b.yml"), dir.join("c.json")]
+        );
+        assert!(rule_files(&dir.join("missing")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rule_id_prefix() {
+        assert_eq!(
+            rule_id_prefix(Path::new("/tmp/amplify-runner-a1/ruleset.json")),
+            "tmp.amplify-runner-a1"
+        );
+        assert_eq!(
+            rule_id_prefix(Path::new(".amplify/rules/internal.yml")),
+            "amplify.rules"
+        );
+        assert_eq!(rule_id_prefix(Path::new("internal.yml")), "");
+    }
+
+    #[test]
+    fn test_tag_rule_sources() {
+        let sources = RuleSources {
+            amplify: "tmp.amplify-runner-a1".to_owned(),
+            custom: vec!["amplify.rules".to_owned()],
+        };
+        let mut report = Report::parse(
+            r#"{"results": [
+                {"check_id": "tmp.amplify-runner-a1.no-eval", "extra": {"metadata": {"cwe": "CWE-95"}}},
+                {"check_id": "amplify.rules.no-eval", "extra": {}},
+                {"check_id": "no-eval"},
+                {"check_id": "tmp.amplify-runner-a10.no-eval"}
+            ]}"#,
+        )
+        .unwrap();
+
+        tag_rule_sources(&mut report, &sources);
+
+        let tags: Vec<&serde_json::Value> = report
+            .results
+            .iter()
+            .map(|finding| &finding.extra.metadata["rule_source"])
+            .collect();
+        // The second is a custom rule with the same ID as an Amplify rule.
+        assert_eq!(tags, ["amplify", "custom", "custom", "custom"]);
+        assert_eq!(report.results[0].extra.metadata["cwe"], "CWE-95");
+        let rule_ids: Vec<&str> = report
+            .results
+            .iter()
+            .map(|finding| finding.check_id.as_str())
+            .collect();
+        assert_eq!(
+            rule_ids,
+            [
+                "no-eval",
+                "no-eval",
+                "no-eval",
+                "tmp.amplify-runner-a10.no-eval"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_longest_prefix_wins() {
+        // Without a work directory, the ruleset is at the root and its
+        // rules have no prefix at all.
+        let sources = RuleSources::new(Path::new("/ruleset.json"), &[]);
+        assert_eq!(sources.source("no-eval"), (RULE_SOURCE_AMPLIFY, "no-eval"));
+
+        let sources = RuleSources {
+            amplify: String::new(),
+            custom: vec!["rules".to_owned(), "rules.internal".to_owned()],
+        };
+        assert_eq!(
+            sources.source("rules.internal.no-eval"),
+            (RULE_SOURCE_CUSTOM, "no-eval")
+        );
+        assert_eq!(sources.source("no-eval"), (RULE_SOURCE_AMPLIFY, "no-eval"));
+    }
+}
@@ -0,0 +1,630 @@
+//! Running tool processes within time and resource limits.
+//!
+//! Every tool runs in its own process group, so that a tool that runs out of
+//! time is stopped along with everything it started: first with `SIGTERM`,
+//! then with `SIGKILL` if the group is still around after a grace period.
+//! Anything a tool leaves running in its group when it exits is killed too,
+//! since it could otherwise keep the tool's pipes open forever.
+//! Memory and CPU limits are set with `setrlimit` before the tool starts and
+//! are inherited by its child processes.
+//!
+//! A tool's stderr is printed live, prefixed with its name and, in a
+//! monorepo, the project it scans, so that progress and errors show up
+//! while it runs. Its stdout is the report that becomes the artifact, and
+//! is capped at the maximum artifact size.
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use std::collections::{BTreeSet, VecDeque};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::amplify::ToolOptions;
+use crate::tools::RunContext;
+
+/// How long a tool has to exit after `SIGTERM` before it's killed.
+const TERMINATE_GRACE: Duration = Duration::from_secs(10);
+
+/// How long to wait for the rest of a tool's output once it has exited.
+/// Processes that left the tool's group can keep its pipes open
+/// indefinitely.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Time between the soft CPU limit, which sends `SIGXCPU`, and the hard
+/// limit, which sends `SIGKILL`.
+const CPU_LIMIT_GRACE: u64 = 5;
+
+/// How many of the last lines of stderr to keep in a timeout error.
+const STDERR_TAIL_LINES: usize = 40;
+
+/// Size above which stdout is written to a file instead of kept in memory.
+const SPILL_THRESHOLD_BYTES: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ToolError {
+    /// The tool ran past its own timeout or the run's, and was stopped.
+    #[error("{tool} did not finish within {} seconds and was stopped.{}", .limit.as_secs(), stderr_section(.stderr))]
+    TimedOut {
+        tool: String,
+        limit: Duration,
+        /// The end of what the tool wrote to stderr before it was stopped.
+        stderr: String,
+    },
+    /// The tool's report is bigger than the maximum artifact size.
+    #[error("{tool} wrote a {} MB report, over the maximum artifact size of {} MB. Exclude more paths, or raise `max_artifact_mb`.", .len.div_ceil(MEGABYTE), .max_bytes / MEGABYTE)]
+    OutputTooLarge {
+        tool: String,
+        len: u64,
+        max_bytes: u64,
+    },
+}
+
+const MEGABYTE: u64 = 1024 * 1024;
+
+fn stderr_section(stderr: &str) -> String {
+    if stderr.is_empty() {
+        String::new()
+    } else {
+        format!("\nLast output on stderr:\n{stderr}")
+    }
+}
+
+/// Limits that a tool process runs under.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Limits {
+    /// Wall-clock time the tool may run for.
+    pub timeout: Option<Duration>,
+    /// Address space the tool may use, in bytes.
+    pub memory_bytes: Option<u64>,
+    /// CPU time the tool may use, in seconds.
+    pub cpu_seconds: Option<u64>,
+    /// Size of the report that the tool may write to stdout, in bytes.
+    pub max_output_bytes: Option<u64>,
+}
+
+impl Limits {
+    /// Limits for a tool with `options`, whose timeout is cut short if the
+    /// run has to be finished by the context's deadline.
+    pub fn new(options: &ToolOptions, ctx: &RunContext) -> Limits {
+        let remaining = ctx
+            .deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        let timeout = match (options.timeout(), remaining) {
+            (Some(timeout), Some(remaining)) => Some(timeout.min(remaining)),
+            (timeout, remaining) => timeout.or(remaining),
+        };
+        Limits {
+            timeout,
+            memory_bytes: options
+                .max_memory_mb
+                .map(|megabytes| megabytes.saturating_mul(MEGABYTE)),
+            cpu_seconds: options.max_cpu_seconds,
+            max_output_bytes: ctx.max_artifact_bytes,
+        }
+    }
+}
+
+/// Process groups of the tools that are running, so they can be killed when
+/// the runner exits without unwinding.
+static RUNNING_GROUPS: Mutex<BTreeSet<libc::pid_t>> = Mutex::new(BTreeSet::new());
+
+/// Kill every tool that is still running. Tools run in their own process
+/// groups, so they don't get the signals that stop the runner.
+pub fn kill_running() {
+    let groups = RUNNING_GROUPS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    for pgid in groups.iter() {
+        // SAFETY: kill has no memory safety requirements.
+        unsafe {
+            libc::kill(-pgid, libc::SIGKILL);
+        }
+    }
+}
+
+/// Sends a signal to a tool's process group, and kills the group if the
+/// tool is abandoned before it exits, e.g. when its task is aborted.
+struct ProcessGroup {
+    pgid: Option<libc::pid_t>,
+}
+
+impl ProcessGroup {
+    fn new(pgid: Option<libc::pid_t>) -> ProcessGroup {
+        if let Some(pgid) = pgid {
+            RUNNING_GROUPS
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .insert(pgid);
+        }
+        ProcessGroup { pgid }
+    }
+
+    fn signal(&self, signal: libc::c_int) {
+        if let Some(pgid) = self.pgid {
+            // SAFETY: kill has no memory safety requirements.
+            unsafe {
+                libc::kill(-pgid, signal);
+            }
+        }
+    }
+
+    /// Stop tracking the group once its leader has exited, since the ID can
+    /// then be reused.
+    fn release(&mut self) {
+        if let Some(pgid) = self.pgid.take() {
+            RUNNING_GROUPS
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .remove(&pgid);
+        }
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        self.signal(libc::SIGKILL);
+        self.release();
+    }
+}
+
+/// A tool's stdout, kept in memory while it's small and spilled to a file
+/// once it isn't.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stdout {
+    Memory(Vec<u8>),
+    File(PathBuf),
+}
+
+impl Stdout {
+    pub fn reader(&self) -> Result<Box<dyn Read + '_>> {
+        match self {
+            Stdout::Memory(bytes) => Ok(Box::new(bytes.as_slice())),
+            Stdout::File(path) => {
+                let file = File::open(path)
+                    .wrap_err_with(|| format!("Failed to open {}.", path.display()))?;
+                Ok(Box::new(BufReader::new(file)))
+            }
+        }
+    }
+
+    /// Replace the output with `value` as JSON, still in a file if it was
+    /// spilled to one.
+    pub fn write_json(self, value: &impl serde::Serialize) -> Result<Stdout> {
+        match self {
+            Stdout::Memory(_) => Ok(Stdout::Memory(
+                serde_json::to_vec(value).wrap_err("Failed to serialize tool output.")?,
+            )),
+            Stdout::File(path) => {
+                let file = File::create(&path)
+                    .wrap_err_with(|| format!("Failed to create {}.", path.display()))?;
+                let mut writer = BufWriter::new(file);
+                serde_json::to_writer(&mut writer, value)
+                    .wrap_err("Failed to serialize tool output.")?;
+                writer
+                    .flush()
+                    .wrap_err_with(|| format!("Failed to write {}.", path.display()))?;
+                Ok(Stdout::File(path))
+            }
+        }
+    }
+
+    pub fn read_to_string(&self) -> Result<String> {
+        let mut contents = String::new();
+        self.reader()?
+            .read_to_string(&mut contents)
+            .wrap_err("Tool output is not valid UTF-8.")?;
+        Ok(contents)
+    }
+}
+
+/// What a tool left behind once it exited.
+#[derive(Debug)]
+pub struct ToolOutput {
+    pub status: ExitStatus,
+    pub stdout: Stdout,
+}
+
+/// Run `command` as `tool` within `limits` and collect its stdout, spilling
+/// it to `spill_path` if it gets large. Stderr is printed as it arrives,
+/// with each line prefixed by `tool`.
+pub async fn run(
+    command: &mut Command,
+    tool: &str,
+    limits: Limits,
+    spill_path: Option<PathBuf>,
+) -> Result<ToolOutput> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .kill_on_drop(true);
+    set_resource_limits(command, limits);
+    let mut child = command
+        .spawn()
+        .wrap_err_with(|| format!("Failed to start {tool}."))?;
+    let mut group = ProcessGroup::new(child.id().and_then(|pid| libc::pid_t::try_from(pid).ok()));
+
+    let sink = StdoutSink {
+        spill_path,
+        max_bytes: limits.max_output_bytes,
+        ..StdoutSink::default()
+    };
+    let stdout_reader = read_stdout(child.stdout.take(), sink);
+    let stderr_tail = Arc::new(Mutex::new(VecDeque::new()));
+    let stderr_reader = forward_stderr(child.stderr.take(), tool.to_owned(), stderr_tail.clone());
+
+    let status = match limits.timeout {
+        Some(limit) => match tokio::time::timeout(limit, child.wait()).await {
+            Ok(status) => status,
+            Err(_) => {
+                group.signal(libc::SIGTERM);
+                if tokio::time::timeout(TERMINATE_GRACE, child.wait())
+                    .await
+                    .is_err()
+                {
+                    group.signal(libc::SIGKILL);
+                    let _ = child.wait().await;
+                }
+                group.signal(libc::SIGKILL);
+                group.release();
+                let _ = drain(stderr_reader).await;
+                let stderr = Vec::from(std::mem::take(&mut *stderr_tail.lock().unwrap()));
+                return Err(ToolError::TimedOut {
+                    tool: tool.to_owned(),
+                    limit,
+                    stderr: stderr.join("\n"),
+                }
+                .into());
+            }
+        },
+        None => child.wait().await,
+    }
+    .wrap_err_with(|| format!("Failed to wait for {tool} to finish."))?;
+    group.signal(libc::SIGKILL);
+    group.release();
+
+    let sink = drain(stdout_reader)
+        .await?
+        .ok_or_else(|| eyre!("{tool} exited, but a process it started kept its stdout open."))?
+        .wrap_err_with(|| format!("Failed to read stdout from {tool}."))?;
+    if let Some(stderr) = drain(stderr_reader).await? {
+        stderr.wrap_err_with(|| format!("Failed to read stderr from {tool}."))?;
+    }
+    if let Some(max_bytes) = sink.max_bytes.filter(|&max_bytes| sink.len > max_bytes) {
+        return Err(ToolError::OutputTooLarge {
+            tool: tool.to_owned(),
+            len: sink.len,
+            max_bytes,
+        }
+        .into());
+    }
+    Ok(ToolOutput {
+        status,
+        stdout: sink
+            .finish()
+            .wrap_err_with(|| format!("Failed to save stdout from {tool}."))?,
+    })
+}
+
+/// Wait up to [`DRAIN_TIMEOUT`] for `reader` to reach the end of its pipe,
+/// or stop it and return `None`.
+async fn drain<T>(mut reader: JoinHandle<T>) -> Result<Option<T>> {
+    match tokio::time::timeout(DRAIN_TIMEOUT, &mut reader).await {
+        Ok(joined) => Ok(Some(joined?)),
+        Err(_) => {
+            reader.abort();
+            Ok(None)
+        }
+    }
+}
+
+/// Apply the memory and CPU limits to the process that `command` starts.
+fn set_resource_limits(command: &mut Command, limits: Limits) {
+    let Limits {
+        memory_bytes,
+        cpu_seconds,
+        ..
+    } = limits;
+    if memory_bytes.is_none() && cpu_seconds.is_none() {
+        return;
+    }
+    let check = |result: libc::c_int| {
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    };
+    // SAFETY: the closure runs between fork and exec, and only calls
+    // setrlimit, which is async-signal-safe.
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(bytes) = memory_bytes {
+                let limit = libc::rlimit {
+                    rlim_cur: bytes as libc::rlim_t,
+                    rlim_max: bytes as libc::rlim_t,
+                };
+                check(libc::setrlimit(libc::RLIMIT_AS, &limit))?;
+            }
+            if let Some(seconds) = cpu_seconds {
+                let limit = libc::rlimit {
+                    rlim_cur: seconds as libc::rlim_t,
+                    rlim_max: seconds.saturating_add(CPU_LIMIT_GRACE) as libc::rlim_t,
+                };
+                check(libc::setrlimit(libc::RLIMIT_CPU, &limit))?;
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Collects a tool's stdout: in memory until it grows past
+/// [`SPILL_THRESHOLD_BYTES`], then in `spill_path`. Once it's over
+/// `max_bytes`, the rest is only counted.
+#[derive(Default)]
+struct StdoutSink {
+    spill_path: Option<PathBuf>,
+    max_bytes: Option<u64>,
+    len: u64,
+    memory: Vec<u8>,
+    file: Option<BufWriter<File>>,
+}
+
+impl StdoutSink {
+    fn write(&mut self, chunk: &[u8]) -> io::Result<()> {
+        self.len += chunk.len() as u64;
+        if self.max_bytes.is_some_and(|max_bytes| self.len > max_bytes) {
+            return Ok(());
+        }
+        if let Some(file) = &mut self.file {
+            return file.write_all(chunk);
+        }
+        self.memory.extend_from_slice(chunk);
+        if self.memory.len() > SPILL_THRESHOLD_BYTES {
+            if let Some(path) = &self.spill_path {
+                let mut file = BufWriter::new(File::create(path)?);
+                file.write_all(&std::mem::take(&mut self.memory))?;
+                self.file = Some(file);
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> io::Result<Stdout> {
+        match (self.file, self.spill_path) {
+            (Some(mut file), Some(path)) => {
+                file.flush()?;
+                Ok(Stdout::File(path))
+            }
+            _ => Ok(Stdout::Memory(self.memory)),
+        }
+    }
+}
+
+fn read_stdout<R>(pipe: Option<R>, mut sink: StdoutSink) -> JoinHandle<io::Result<StdoutSink>>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let Some(mut pipe) = pipe else {
+            return Ok(sink);
+        };
+        let mut chunk = vec![0; 64 * 1024];
+        loop {
+            let read = pipe.read(&mut chunk).await?;
+            if read == 0 {
+                return Ok(sink);
+            }
+            sink.write(&chunk[..read])?;
+        }
+    })
+}
+
+/// Print each line from `pipe` as `[tool] line` as soon as it arrives, and
+/// keep the last few lines in `tail` so that they can be reported if the
+/// tool is stopped.
+fn forward_stderr<R>(
+    pipe: Option<R>,
+    tool: String,
+    tail: Arc<Mutex<VecDeque<String>>>,
+) -> JoinHandle<io::Result<()>>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let Some(pipe) = pipe else {
+            return Ok(());
+        };
+        let mut pipe = tokio::io::BufReader::new(pipe);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if pipe.read_until(b'\n', &mut line).await? == 0 {
+                return Ok(());
+            }
+            let line = String::from_utf8_lossy(&line).trim_end().to_owned();
+            println!("[{tool}] {line}");
+            let mut tail = tail.lock().unwrap();
+            if tail.len() == STDERR_TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(line);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_support::TempDir;
+
+    #[test]
+    fn test_tool_label_includes_the_project() {
+        let mut ctx = RunContext::default();
+        assert_eq!(ctx.tool_label("Opengrep"), "Opengrep");
+        ctx.project_path = Some("services/api".into());
+        assert_eq!(ctx.tool_label("Opengrep"), "Opengrep services/api");
+    }
+
+    #[tokio::test]
+    async fn test_output_is_collected() {
+        let output = run(
+            Command::new("sh").args(["-c", "echo out; echo err >&2"]),
+            "sh",
+            Limits::default(),
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(output.stdout, Stdout::Memory(b"out\n".to_vec()));
+    }
+
+    #[test]
+    fn test_running_groups_are_tracked() {
+        // No process has this ID, so nothing is signalled if the group is
+        // dropped before it's released.
+        let pgid = libc::pid_t::MAX;
+        let mut group = ProcessGroup::new(Some(pgid));
+        assert!(RUNNING_GROUPS.lock().unwrap().contains(&pgid));
+
+        group.release();
+        assert!(!RUNNING_GROUPS.lock().unwrap().contains(&pgid));
+    }
+
+    #[tokio::test]
+    async fn test_processes_left_behind_are_stopped() {
+        let started = std::time::Instant::now();
+        // The background sleep holds stdout and stderr open after the shell
+        // exits, and would keep the run waiting if it wasn't stopped.
+        let output = run(
+            Command::new("sh").args(["-c", "sleep 30 & echo done"]),
+            "sh",
+            Limits::default(),
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(output.stdout, Stdout::Memory(b"done\n".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_stops_the_process_group() {
+        let limits = Limits {
+            timeout: Some(Duration::from_millis(200)),
+            ..Limits::default()
+        };
+        let started = std::time::Instant::now();
+        // The sleep is a child of the shell and holds stderr open, so the
+        // error only comes back this quickly if the whole group is stopped.
+        let error = run(
+            Command::new("sh").args(["-c", "echo working >&2; sleep 30; true"]),
+            "sh",
+            limits,
+            None,
+        )
+        .await
+        .unwrap_err();
+
+        assert!(started.elapsed() < TERMINATE_GRACE);
+        match error.downcast_ref::<ToolError>() {
+            Some(ToolError::TimedOut { tool, stderr, .. }) => {
+                assert_eq!(tool, "sh");
+                assert_eq!(stderr, "working");
+            }
+            _ => panic!("expected a timeout, got {error:#}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cpu_limit_is_applied() {
+        let limits = Limits {
+            cpu_seconds: Some(7),
+            ..Limits::default()
+        };
+        let output = run(
+            Command::new("sh").args(["-c", "ulimit -t"]),
+            "sh",
+            limits,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(output.stdout, Stdout::Memory(b"7\n".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_large_output_is_spilled_to_a_file() {
+        let dir = TempDir::new("stdout");
+        let path = dir.join("stdout");
+        let size = SPILL_THRESHOLD_BYTES + 1;
+        let output = run(
+            Command::new("head").args(["-c", &size.to_string(), "/dev/zero"]),
+            "head",
+            Limits::default(),
+            Some(path.clone()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(output.stdout, Stdout::File(path.clone()));
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), size as u64);
+
+        let rewritten = output.stdout.write_json(&[1, 2]).unwrap();
+        assert_eq!(rewritten, Stdout::File(path.clone()));
+        assert_eq!(rewritten.read_to_string().unwrap(), "[1,2]");
+    }
+
+    #[tokio::test]
+    async fn test_output_over_the_maximum_is_an_error() {
+        let limits = Limits {
+            max_output_bytes: Some(4),
+            ..Limits::default()
+        };
+        let error = run(Command::new("echo").arg("too long"), "echo", limits, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ToolError>(),
+            Some(ToolError::OutputTooLarge { len: 9, .. })
+        ));
+    }
+
+    #[test]
+    fn test_limits_respect_the_run_deadline() {
+        let options = ToolOptions {
+            timeout: Some(600),
+            max_memory_mb: Some(512),
+            ..ToolOptions::default()
+        };
+        let mut ctx = RunContext {
+            max_artifact_bytes: Some(1024),
+            ..RunContext::default()
+        };
+        assert_eq!(
+            Limits::new(&options, &ctx),
+            Limits {
+                timeout: Some(Duration::from_secs(600)),
+                memory_bytes: Some(512 * 1024 * 1024),
+                cpu_seconds: None,
+                max_output_bytes: Some(1024),
+            }
+        );
+
+        ctx.deadline = Some(Instant::now() + Duration::from_secs(60));
+        let timeout = Limits::new(&options, &ctx).timeout.unwrap();
+        assert!(timeout <= Duration::from_secs(60));
+        assert!(Limits::new(&ToolOptions::default(), &ctx).timeout.is_some());
+    }
+}
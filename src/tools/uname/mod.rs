@@ -0,0 +1,50 @@
+//! Placeholder tool that reports the host's `uname -a`.
+
+use color_eyre::eyre::Result;
+use tokio::process::Command;
+
+use crate::amplify::{Artifact, ArtifactType, ToolOptions};
+use crate::common::ToolLog;
+use crate::profile::RepoProfile;
+use crate::tools::process::{self, Limits, Stdout};
+use crate::tools::{RunContext, ToolActions};
+
+#[derive(Debug, Default)]
+pub struct Uname {
+    options: ToolOptions,
+}
+
+impl Uname {
+    pub fn new(options: ToolOptions) -> Self {
+        Uname { options }
+    }
+}
+
+impl ToolActions for Uname {
+    fn name(&self) -> &'static str {
+        "uname"
+    }
+
+    fn skip_reason(&self, _profile: &RepoProfile) -> Option<String> {
+        None
+    }
+
+    async fn setup(&self, _ctx: &RunContext, log: &ToolLog) -> Result<()> {
+        log.println("Attempted setup function for uname.");
+        Ok(())
+    }
+
+    async fn launch(&self, ctx: &RunContext, log: &ToolLog) -> Result<Artifact> {
+        let mut uname = Command::new("uname");
+        uname.args(["-a"]).args(&self.options.extra_args);
+        log.println("Pushed off request for uname.");
+        let limits = Limits::new(&self.options, ctx);
+        let output = process::run(&mut uname, &ctx.tool_label("uname"), limits, None).await?;
+        log.println(output.stdout.read_to_string()?.trim_end());
+        log.println("Finished running uname.");
+        Ok(Artifact::new(
+            ArtifactType::Json,
+            Stdout::Memory(Vec::new()),
+        ))
+    }
+}
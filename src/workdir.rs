@@ -0,0 +1,187 @@
+//! Per-run working directory for downloaded binaries, rules and other
+//! intermediate files.
+//!
+//! By default a fresh directory is created under the system's temporary
+//! directory and removed when the run ends. It gets a random name and is
+//! only accessible to the runner's user, so that other users of a shared
+//! machine can't create it first or swap the tools and rules inside it.
+//! When `--work-dir` or `AMPLIFY_WORK_DIR` is set, that directory is used
+//! instead and left in place afterwards, so its contents can be inspected.
+
+use color_eyre::eyre::{Result, WrapErr};
+use std::ffi::{CString, OsString};
+use std::io::Write;
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+const ENV_WORK_DIR: &str = "AMPLIFY_WORK_DIR";
+
+#[derive(Debug)]
+pub struct WorkDir {
+    root: PathBuf,
+    /// Remove `root` when the run ends.
+    cleanup: bool,
+    /// Use the fixed paths that the Docker image pre-creates.
+    legacy: bool,
+}
+
+impl Default for WorkDir {
+    fn default() -> Self {
+        WorkDir::legacy()
+    }
+}
+
+impl WorkDir {
+    /// Set up the working directory for this run, from `--work-dir`, then
+    /// `AMPLIFY_WORK_DIR`, then a new temporary directory.
+    pub fn create(cli: Option<PathBuf>) -> Result<WorkDir> {
+        if let Some(root) = cli.or_else(|| std::env::var_os(ENV_WORK_DIR).map(PathBuf::from)) {
+            std::fs::create_dir_all(&root)
+                .wrap_err_with(|| format!("Failed to create work directory {}.", root.display()))?;
+            return Ok(WorkDir {
+                root,
+                cleanup: false,
+                legacy: false,
+            });
+        }
+        match create_private_dir(&std::env::temp_dir()) {
+            Ok(root) => Ok(WorkDir {
+                root,
+                cleanup: true,
+                legacy: false,
+            }),
+            Err(e) => {
+                println!(
+                    "::warning::Could not create a work directory in {} ({e}), falling back to /usr/bin and /.",
+                    std::env::temp_dir().display()
+                );
+                Ok(WorkDir::legacy())
+            }
+        }
+    }
+
+    /// The paths used before the runner had a working directory: binaries
+    /// in `/usr/bin` and everything else in `/`. These only work in the
+    /// Docker image, which creates the files up front with the runner's
+    /// user as their owner.
+    pub fn legacy() -> WorkDir {
+        WorkDir {
+            root: PathBuf::from("/"),
+            cleanup: false,
+            legacy: true,
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Where a downloaded tool binary named `name` is installed.
+    pub fn binary(&self, name: &str) -> PathBuf {
+        if self.legacy {
+            Path::new("/usr/bin").join(name)
+        } else {
+            self.root.join("bin").join(name)
+        }
+    }
+
+    /// Where an intermediate file named `name` is written.
+    pub fn file(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+
+    /// Write an executable to `path`, replacing any existing file.
+    pub fn install_binary(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .wrap_err_with(|| format!("Failed to create {}.", dir.display()))?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o755)
+            .open(path)
+            .wrap_err_with(|| format!("Failed to create {}.", path.display()))?;
+        file.write_all(contents)
+            .wrap_err_with(|| format!("Failed to write {}.", path.display()))
+    }
+}
+
+/// Create a new directory in `parent` with a random name and mode 0700, as
+/// `mkdtemp(3)` does. This never reuses a directory that already exists.
+fn create_private_dir(parent: &Path) -> std::io::Result<PathBuf> {
+    let template = parent.join("amplify-runner-XXXXXX").into_os_string();
+    let mut template = CString::new(template.into_vec())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?
+        .into_bytes_with_nul();
+    // SAFETY: `template` is a NUL-terminated buffer, which mkdtemp only
+    // changes in place.
+    let created = unsafe { libc::mkdtemp(template.as_mut_ptr().cast()) };
+    if created.is_null() {
+        return Err(std::io::Error::last_os_error());
+    }
+    template.pop();
+    Ok(PathBuf::from(OsString::from_vec(template)))
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        if self.cleanup {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_support::TempDir;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_legacy_paths() {
+        let work_dir = WorkDir::legacy();
+        assert_eq!(work_dir.binary("opengrep"), Path::new("/usr/bin/opengrep"));
+        assert_eq!(work_dir.file("ruleset.json"), Path::new("/ruleset.json"));
+    }
+
+    #[test]
+    fn test_given_work_dir_is_kept() {
+        let root = TempDir::new("work");
+        let work_dir = WorkDir::create(Some(root.to_path_buf())).unwrap();
+        let binary = work_dir.binary("tool");
+        work_dir.install_binary(&binary, b"#!/bin/sh\n").unwrap();
+
+        assert_eq!(binary, root.join("bin/tool"));
+        let mode = std::fs::metadata(&binary).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+        drop(work_dir);
+        assert!(root.exists());
+    }
+
+    #[tokio::test]
+    async fn test_temporary_work_dir_is_removed() {
+        let _lock = crate::common::test_support::ENV_MUTEX.lock().await;
+        std::env::remove_var(ENV_WORK_DIR);
+        let work_dir = WorkDir::create(None).unwrap();
+        let root = work_dir.root().to_path_buf();
+        std::fs::write(work_dir.file("ruleset.json"), "{}").unwrap();
+
+        drop(work_dir);
+
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn test_temporary_work_dir_is_private_and_new() {
+        let parent = TempDir::new("private");
+        let first = create_private_dir(&parent).unwrap();
+        let second = create_private_dir(&parent).unwrap();
+
+        assert_ne!(first, second);
+        let mode = std::fs::metadata(&first).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+}